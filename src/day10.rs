@@ -22,22 +22,36 @@ pub enum Pipes {
 }
 
 impl Pipes {
-  pub fn next(&self, pos: (isize, isize), last: (isize, isize)) -> (isize, isize) {
-    let neighbors = self.neighbors(pos);
-    *neighbors.iter().find(|&&p| p != last).unwrap()
+  const SHAPES: [Pipes; 6] = [
+    Pipes::Vertical,
+    Pipes::Horizontal,
+    Pipes::NorthEast,
+    Pipes::NorthWest,
+    Pipes::SouthWest,
+    Pipes::SouthEast,
+  ];
+
+  pub fn next(&self, pos: (isize, isize), last: (isize, isize)) -> Option<(isize, isize)> {
+    self.neighbors(pos)?.into_iter().find(|&p| p != last)
   }
 
-  pub fn neighbors(&self, (x, y): (isize, isize)) -> [(isize, isize); 2] {
+  /// The two tiles this pipe connects to, or `None` for a start tile whose
+  /// shape hasn't been worked out.
+  pub fn neighbors(&self, (x, y): (isize, isize)) -> Option<[(isize, isize); 2]> {
     match self {
-      Pipes::Vertical => [(x, y - 1), (x, y + 1)],
-      Pipes::Horizontal => [(x - 1, y), (x + 1, y)],
-      Pipes::NorthEast => [(x, y - 1), (x + 1, y)],
-      Pipes::NorthWest => [(x, y - 1), (x - 1, y)],
-      Pipes::SouthWest => [(x, y + 1), (x - 1, y)],
-      Pipes::SouthEast => [(x, y + 1), (x + 1, y)],
-      Pipes::Start => unreachable!(),
+      Pipes::Vertical => Some([(x, y - 1), (x, y + 1)]),
+      Pipes::Horizontal => Some([(x - 1, y), (x + 1, y)]),
+      Pipes::NorthEast => Some([(x, y - 1), (x + 1, y)]),
+      Pipes::NorthWest => Some([(x, y - 1), (x - 1, y)]),
+      Pipes::SouthWest => Some([(x, y + 1), (x - 1, y)]),
+      Pipes::SouthEast => Some([(x, y + 1), (x + 1, y)]),
+      Pipes::Start => None,
     }
   }
+
+  pub fn connects(&self, from: (isize, isize), to: (isize, isize)) -> bool {
+    self.neighbors(from).is_some_and(|n| n.contains(&to))
+  }
 }
 
 impl Display for Pipes {
@@ -77,14 +91,22 @@ impl TryFrom<char> for Pipes {
 
 pub struct Grid {
   pipes: HashMap<(isize, isize), Pipes>,
+  start: (isize, isize),
   path: HashSet<(isize, isize)>,
+  /// The tiles enclosed by the loop, once `enclosed` has been run.
+  inside: Option<HashSet<(isize, isize)>>,
+  size: (isize, isize),
 }
 
 impl FromStr for Grid {
   type Err = anyhow::Error;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    let pipes = s
+    let size = (
+      s.lines().map(|line| line.len()).max().unwrap_or(0) as isize,
+      s.lines().count() as isize,
+    );
+    let mut pipes: HashMap<(isize, isize), Pipes> = s
       .lines()
       .enumerate()
       .flat_map(|(y, line)| {
@@ -99,21 +121,58 @@ impl FromStr for Grid {
       })
       .collect::<Result<_, _>>()?;
 
+    let starts: Vec<_> = pipes
+      .iter()
+      .filter(|&(_, &pipe)| pipe == Pipes::Start)
+      .map(|(&pos, _)| pos)
+      .collect();
+    let start = match starts[..] {
+      [start] => start,
+      [] => return Err(parse_error!("No start position in map").into()),
+      _ => return Err(parse_error!("Found {} start positions", starts.len()).into()),
+    };
+    pipes.insert(start, Grid::start_shape(&pipes, start)?);
+
     Ok(Self {
       pipes,
+      start,
       path: HashSet::new(),
+      inside: None,
+      size,
     })
   }
 }
 
 impl Grid {
   pub fn start(&self) -> (isize, isize) {
-    *self
-      .pipes
-      .iter()
-      .find(|&(_, &v)| v == Pipes::Start)
-      .expect("No start position in map")
-      .0
+    self.start
+  }
+
+  /// Work out what shape of pipe is hidden under the start tile from the
+  /// neighbouring pipes which connect back to it.
+  fn start_shape(
+    pipes: &HashMap<(isize, isize), Pipes>,
+    (x, y): (isize, isize),
+  ) -> Result<Pipes, ParseError> {
+    let connected: Vec<_> = [(x, y - 1), (x, y + 1), (x - 1, y), (x + 1, y)]
+      .into_iter()
+      .filter(|pos| pipes.get(pos).is_some_and(|p| p.connects(*pos, (x, y))))
+      .collect();
+
+    if connected.len() != 2 {
+      return Err(parse_error!(
+        "Start at {:?} connects to {} pipes, expected 2",
+        (x, y),
+        connected.len()
+      ));
+    }
+
+    Ok(
+      *Pipes::SHAPES
+        .iter()
+        .find(|shape| connected.iter().all(|&pos| shape.connects((x, y), pos)))
+        .expect("Every pair of directions has a pipe shape"),
+    )
   }
 
   pub fn set(&mut self, pos: (isize, isize), value: Pipes) {
@@ -121,17 +180,18 @@ impl Grid {
   }
 
   pub fn loop_size(&mut self, start: (isize, isize)) -> usize {
-    // let mut seen = HashSet::new();
-    // let start = from;
     let mut current = start;
-    let mut last = *self.pipes[&start].neighbors(start).first().unwrap();
+    let mut last = self.pipes[&start]
+      .neighbors(start)
+      .expect("Start shape is inferred when parsing")[0];
     let mut count = 0;
     self.path.clear();
+    self.inside = None;
 
     loop {
       self.path.insert(current);
       let pipe = self.pipes[&current];
-      let next = pipe.next(current, last);
+      let next = pipe.next(current, last).expect("Loop is broken");
       last = current;
       current = next;
       count += 1;
@@ -140,23 +200,56 @@ impl Grid {
       }
     }
   }
+
+  /// Return the tiles enclosed by the loop through `start`.
+  ///
+  /// Scanning each row from the left, a tile is inside if we've crossed the
+  /// loop an odd number of times. Only loop pipes which connect north count
+  /// as a crossing: a run like `L--7` crosses once while `L--J` doesn't, so
+  /// gaps squeezed between adjacent pipes are handled without any flood fill.
+  pub fn enclosed(&mut self, start: (isize, isize)) -> &HashSet<(isize, isize)> {
+    self.loop_size(start);
+    let mut enclosed = HashSet::new();
+
+    for y in 0..self.size.1 {
+      let mut inside = false;
+      for x in 0..self.size.0 {
+        if self.path.contains(&(x, y)) {
+          let pipe = self.pipes[&(x, y)];
+          if pipe.connects((x, y), (x, y - 1)) {
+            inside = !inside;
+          }
+        } else if inside {
+          enclosed.insert((x, y));
+        }
+      }
+    }
+
+    self.inside.insert(enclosed)
+  }
 }
 
 impl Display for Grid {
+  /// Draw the grid with the loop in red. Once `enclosed` has been run, tiles
+  /// off the loop are drawn as `I` (green) inside it or `O` (blue) outside.
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    let max_x = self.pipes.keys().max_by_key(|e| e.0).unwrap().0;
-    let max_y = self.pipes.keys().max_by_key(|e| e.1).unwrap().1;
-
-    for y in 0..=max_y {
-      for x in 0..=max_x {
-        if let Some(p) = self.pipes.get(&(x, y)) {
-          if self.path.contains(&(x, y)) {
-            write!(f, "{}", format!("{}", p).red())?;
+    for y in 0..self.size.1 {
+      for x in 0..self.size.0 {
+        let tile = match self.pipes.get(&(x, y)) {
+          Some(p) => format!("{}", p),
+          None => ".".to_string(),
+        };
+
+        if self.path.contains(&(x, y)) {
+          write!(f, "{}", tile.red())?;
+        } else if let Some(inside) = &self.inside {
+          if inside.contains(&(x, y)) {
+            write!(f, "{}", "I".green())?;
           } else {
-            write!(f, "{}", p)?;
+            write!(f, "{}", "O".blue())?;
           }
         } else {
-          write!(f, ".")?;
+          write!(f, "{}", tile)?;
         }
       }
       writeln!(f)?;
@@ -181,7 +274,6 @@ LJ.LJ";
     let mut g: Grid = EXAMPLE_INPUT.parse().expect("Failed to parse grid");
     let s = g.start();
     println!("{}", g);
-    g.set(s, Pipes::SouthEast);
     assert_eq!(g.loop_size(s), 16);
   }
 
@@ -189,15 +281,100 @@ LJ.LJ";
   fn part1_solution() {
     let mut g: Grid = INPUT.parse().expect("Failed to parse grid");
     let s = g.start();
-    g.set(s, Pipes::Horizontal);
     let ans = g.loop_size(s) / 2;
     println!("{}", g);
     assert_eq!(ans, 7145);
   }
 
+  const ENCLOSED_INPUT: &'static str = "...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........";
+
+  const SQUEEZED_INPUT: &'static str = "..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........";
+
+  const LARGER_INPUT: &'static str = ".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...";
+
   #[test]
-  fn part2_example() {}
+  fn part2_example() {
+    for (input, expected) in [(ENCLOSED_INPUT, 4), (SQUEEZED_INPUT, 4), (LARGER_INPUT, 8)] {
+      let mut g: Grid = input.parse().expect("Failed to parse grid");
+      let s = g.start();
+      assert_eq!(g.enclosed(s).len(), expected);
+    }
+
+    let mut g: Grid = SQUEEZED_INPUT.parse().expect("Failed to parse grid");
+    let s = g.start();
+    let inside = g.enclosed(s);
+    // the gap between the two `||` runs is outside
+    assert!(!inside.contains(&(4, 6)));
+    assert!(inside.contains(&(2, 6)));
+
+    // a loop enclosing nothing still marks the tiles outside it
+    let mut g: Grid = "F7.\nSJ.".parse().expect("Failed to parse grid");
+    let s = g.start();
+    assert_eq!(g.to_string(), "┌┐.\n└┘.\n");
+    assert!(g.enclosed(s).is_empty());
+    assert_eq!(
+      g.to_string(),
+      format!(
+        "{}{}{}\n{}{}{}\n",
+        "┌".red(),
+        "┐".red(),
+        "O".blue(),
+        "└".red(),
+        "┘".red(),
+        "O".blue()
+      )
+    );
+  }
 
   #[test]
-  fn part2_solution() {}
+  fn part2_solution() {
+    let mut g: Grid = INPUT.parse().expect("Failed to parse grid");
+    let s = g.start();
+    let ans = g.enclosed(s).len();
+    assert_eq!(ans, 445);
+  }
+
+  #[test]
+  fn start_inference() {
+    let shape = |input: &str| {
+      let g: Grid = input.parse().expect("Failed to parse grid");
+      g.pipes[&g.start()]
+    };
+
+    assert_eq!(shape(EXAMPLE_INPUT), Pipes::SouthEast);
+    assert_eq!(shape(LARGER_INPUT), Pipes::SouthEast);
+    assert_eq!(shape(INPUT), Pipes::Horizontal);
+    assert_eq!(shape(".|.\n-S.\n..."), Pipes::NorthWest);
+
+    assert!(".....\n..S..\n.....".parse::<Grid>().is_err());
+    assert!(".|.\n-S-\n.|.".parse::<Grid>().is_err());
+    assert!("-S.".parse::<Grid>().is_err());
+    assert!("S-S".parse::<Grid>().is_err());
+    assert!("F7\nLJ".parse::<Grid>().is_err());
+  }
 }
@@ -65,6 +65,117 @@ impl Game {
     let (r, g, b) = self.min_blocks();
     r * g * b
   }
+
+  /// Return the smallest bag which makes every one of the given games possible.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day2::Game;
+  ///
+  /// let games = Game::load_all("Game 1: 3 blue, 1 red\nGame 2: 2 green; 4 red").unwrap();
+  ///
+  /// assert_eq!(Game::min_bag(&games), (4, 2, 3));
+  /// ```
+  pub fn min_bag(games: &[Game]) -> (usize, usize, usize) {
+    games
+      .iter()
+      .map(|game| game.min_blocks())
+      .fold((0, 0, 0), |(ar, ag, ab), (r, g, b)| {
+        (ar.max(r), ag.max(g), ab.max(b))
+      })
+  }
+
+  /// Return every play in the given games which rules out the supplied bag,
+  /// along with the colour(s) that exceeded it.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day2::{Color, Game, Violation};
+  ///
+  /// let games = Game::load_all("Game 1: 3 blue\nGame 2: 1 red; 5 green, 4 blue").unwrap();
+  /// let v = Game::violations(&games, 1, 4, 3);
+  ///
+  /// assert_eq!(v, vec![
+  ///   Violation { game: 2, play: 1, color: Color::Green, count: 5 },
+  ///   Violation { game: 2, play: 1, color: Color::Blue, count: 4 },
+  /// ]);
+  /// ```
+  pub fn violations(games: &[Game], r: usize, g: usize, b: usize) -> Vec<Violation> {
+    games
+      .iter()
+      .flat_map(|game| {
+        game
+          .plays
+          .iter()
+          .enumerate()
+          .filter(|(_, play)| !play.is_possible(r, g, b))
+          .flat_map(move |(index, play)| {
+            [
+              (Color::Red, play.r, r),
+              (Color::Green, play.g, g),
+              (Color::Blue, play.b, b),
+            ]
+            .into_iter()
+            .filter(|&(_, count, limit)| count > limit)
+            .map(move |(color, count, _)| Violation {
+              game: game.id,
+              play: index,
+              color,
+              count,
+            })
+          })
+      })
+      .collect()
+  }
+
+  /// Rank candidate bags by the number of games each one makes possible,
+  /// most permissive first. Bags admitting the same number of games keep
+  /// their original order.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day2::Game;
+  ///
+  /// let games = Game::load_all("Game 1: 3 blue\nGame 2: 1 red; 2 green").unwrap();
+  /// let ranked = Game::rank_bags(&games, &[(0, 0, 3), (1, 2, 3), (1, 2, 0)]);
+  ///
+  /// assert_eq!(ranked, vec![((1, 2, 3), 2), ((0, 0, 3), 1), ((1, 2, 0), 1)]);
+  /// ```
+  pub fn rank_bags(
+    games: &[Game],
+    bags: &[(usize, usize, usize)],
+  ) -> Vec<((usize, usize, usize), usize)> {
+    let mut ranked: Vec<_> = bags
+      .iter()
+      .map(|&(r, g, b)| {
+        let admitted = games
+          .iter()
+          .filter(|game| game.is_possible(r, g, b))
+          .count();
+        ((r, g, b), admitted)
+      })
+      .collect();
+
+    ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+    ranked
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+  Red,
+  Green,
+  Blue,
+}
+
+/// A single colour in a single play which exceeded the bag being tested.
+/// `play` is the zero-based index of the play within its game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+  pub game: usize,
+  pub play: usize,
+  pub color: Color,
+  pub count: usize,
 }
 
 impl FromStr for Game {
@@ -199,4 +310,28 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green"#;
 
     assert_eq!(sum, 59795);
   }
+
+  #[test]
+  fn bag_inference_example() {
+    let games = Game::load_all(EXAMPLE_INPUT).expect("Failed to parse games");
+    let bag = Game::min_bag(&games);
+
+    assert_eq!(bag, (20, 13, 15));
+    assert!(Game::violations(&games, bag.0, bag.1, bag.2).is_empty());
+
+    let v = Game::violations(&games, 12, 13, 14);
+    let mut ruled_out: Vec<usize> = v.iter().map(|v| v.game).collect();
+    ruled_out.dedup();
+
+    assert_eq!(ruled_out, vec![3, 4]);
+    assert_eq!(
+      v[0],
+      Violation {
+        game: 3,
+        play: 0,
+        color: Color::Red,
+        count: 20
+      }
+    );
+  }
 }
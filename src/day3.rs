@@ -1,5 +1,6 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
+use colored::Colorize;
 use regex::Regex;
 
 const INPUT: &'static str = include_str!("../inputs/day3.txt");
@@ -36,65 +37,346 @@ impl Part {
   pub fn is_symbol(&self) -> bool {
     !self.is_number()
   }
+
+  pub fn number(&self) -> Option<usize> {
+    match self.value {
+      PartValue::Number(n) => Some(n),
+      _ => None,
+    }
+  }
+}
+
+/// A `*` symbol with exactly two adjacent part numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gear {
+  pub pos: (isize, isize),
+  pub parts: (usize, usize),
+  pub ratio: usize,
 }
 
 #[derive(Debug)]
 pub struct Engine {
   pub parts: HashMap<(isize, isize), Part>,
+  /// Every occupied cell, mapped to the starting cell of the part covering it.
+  cells: HashMap<(isize, isize), (isize, isize)>,
+  size: (isize, isize),
+  blank: char,
+  /// Characters which are neither parts nor blank, kept only for rendering.
+  ignored: HashMap<(isize, isize), char>,
+  neighborhood: Neighborhood,
+}
+
+/// How each part of the schematic is classified when rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Annotation {
+  /// A number adjacent to at least one symbol.
+  Part,
+  /// A number not adjacent to any symbol.
+  Loose,
+  /// A `*` with exactly two adjacent numbers.
+  Gear,
+  /// A `*` which is not a gear.
+  Star,
+  Symbol,
+}
+
+impl Display for Annotation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let label = match self {
+      Annotation::Part => "part",
+      Annotation::Loose => "loose",
+      Annotation::Gear => "gear",
+      Annotation::Star => "star",
+      Annotation::Symbol => "symbol",
+    };
+    f.pad(label)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+  /// The schematic with each annotation in its own colour.
+  Color,
+  /// The uncoloured schematic, followed by a legend listing the annotation
+  /// of every part.
+  Legend,
+}
+
+/// Which cells count as adjacent to a part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+  /// Only the cells directly above, below, left and right.
+  Orthogonal,
+  /// All eight surrounding cells, including diagonals.
+  Moore,
+  /// Every cell within the given number of steps in any direction,
+  /// including diagonals.
+  Radius(usize),
+}
+
+/// Controls how a schematic is parsed and how adjacency is decided.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+  /// The character used for empty cells.
+  pub blank: char,
+  /// The characters treated as symbols. `None` treats every character other
+  /// than a digit or the blank as a symbol; anything else is ignored.
+  pub symbols: Option<Vec<char>>,
+  pub neighborhood: Neighborhood,
+  /// Whether a number reaching the end of a line continues onto the next.
+  pub wrap_numbers: bool,
+}
+
+impl Default for EngineConfig {
+  fn default() -> Self {
+    Self {
+      blank: '.',
+      symbols: None,
+      neighborhood: Neighborhood::Moore,
+      wrap_numbers: false,
+    }
+  }
+}
+
+impl EngineConfig {
+  fn is_symbol(&self, ch: char) -> bool {
+    ch != self.blank
+      && !ch.is_ascii_digit()
+      && self.symbols.as_ref().is_none_or(|s| s.contains(&ch))
+  }
 }
 
 impl FromStr for Engine {
   type Err = &'static str;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Engine::parse_with(s, &EngineConfig::default())
+  }
+}
+
+impl Engine {
+  /// Parse a schematic using the given configuration.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day3::{Engine, EngineConfig, Neighborhood};
+  ///
+  /// let config = EngineConfig {
+  ///   blank: '_',
+  ///   neighborhood: Neighborhood::Orthogonal,
+  ///   ..Default::default()
+  /// };
+  /// let e = Engine::parse_with("12_\n__#\n_3#", &config).unwrap();
+  ///
+  /// assert_eq!(e.symbol_adjacent_parts(), vec![3]);
+  /// ```
+  pub fn parse_with(s: &str, config: &EngineConfig) -> Result<Self, &'static str> {
+    let lines: Vec<&str> = s.trim().lines().collect();
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let size = (width as isize, lines.len() as isize);
+    let r = Regex::new(r"[0-9]+").expect("Failed to compile regex");
+
+    // pad every line to the full width so that a linear offset into a row
+    // (or into all rows joined, when wrapping) maps directly onto a cell.
+    let rows: Vec<String> = lines
+      .iter()
+      .map(|l| {
+        let pad = width - l.chars().count();
+        l.chars()
+          .chain(std::iter::repeat_n(config.blank, pad))
+          .collect()
+      })
+      .collect();
+    let haystacks: Vec<(usize, String)> = if config.wrap_numbers {
+      vec![(0, rows.concat())]
+    } else {
+      rows.into_iter().enumerate().collect()
+    };
+
     let mut parts = HashMap::new();
-    let r = Regex::new(r"\d+").expect("Failed to compile regex");
+    let mut ignored = HashMap::new();
+    let to_cell = |offset: usize| ((offset % width) as isize, (offset / width) as isize);
 
-    s.trim().lines().enumerate().for_each(|(y, line)| {
-      line
+    for (y, haystack) in haystacks.iter() {
+      let base = y * width;
+
+      haystack
         .chars()
         .enumerate()
-        .filter(|(_, ch)| !ch.is_digit(10) && *ch != '.')
+        .filter(|&(_, ch)| ch != config.blank && !ch.is_ascii_digit())
         .for_each(|(x, ch)| {
-          parts.insert(
-            (x as isize, y as isize),
-            Part {
+          if config.is_symbol(ch) {
+            let part = Part {
               value: PartValue::Symbol(ch),
               len: 1,
-            },
-          );
+            };
+            parts.insert(to_cell(base + x), part);
+          } else {
+            ignored.insert(to_cell(base + x), ch);
+          }
         });
 
-      r.captures_iter(line).for_each(|capture| {
-        let m = capture.get(0).unwrap();
-        let v: usize = m.as_str().parse().unwrap();
+      for m in r.find_iter(haystack) {
+        let v: usize = m
+          .as_str()
+          .parse()
+          .map_err(|_| "Failed to parse part number")?;
         let part = Part {
           value: PartValue::Number(v),
           len: m.len(),
         };
-        let x = m.start();
-        parts.insert((x as isize, y as isize), part);
-      });
-    });
+        parts.insert(to_cell(base + haystack[..m.start()].chars().count()), part);
+      }
+    }
+
+    let mut engine = Engine {
+      parts,
+      cells: HashMap::new(),
+      size,
+      blank: config.blank,
+      ignored,
+      neighborhood: config.neighborhood,
+    };
+    engine.cells = engine
+      .parts
+      .iter()
+      .flat_map(|(&pos, p)| engine.part_cells(pos, p.len).map(move |cell| (cell, pos)))
+      .collect();
 
-    Ok(Engine { parts })
+    Ok(engine)
   }
-}
 
-impl Engine {
-  // pub fn gears(&self) -> Vec<usize> {
-  //   self
-  //     .parts
-  //     .iter()
-  //     .filter(|&(pos, p)|{
-  //       if let PartValue::Symbol(ch) = p.value {
-  //         ch == '*'
-  //       } else {
-  //         false
-  //       }
-  //     })
-
-  // }
+  /// Return every cell covered by a part of `len` cells starting at `pos`,
+  /// continuing onto the next line if the part wraps.
+  fn part_cells(&self, (x, y): (isize, isize), len: usize) -> impl Iterator<Item = (isize, isize)> {
+    let width = self.size.0.max(1);
+    (0..len as isize).map(move |i| ((x + i) % width, y + (x + i) / width))
+  }
+
+  /// Return every gear in the schematic, ordered by position (top to bottom,
+  /// left to right).
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day3::Engine;
+  ///
+  /// let e: Engine = "12.3\n.*..\n..45".parse().unwrap();
+  /// let gears = e.gears();
+  ///
+  /// assert_eq!(gears.len(), 1);
+  /// assert_eq!(gears[0].pos, (1, 1));
+  /// assert_eq!(gears[0].ratio, 12 * 45);
+  /// ```
+  pub fn gears(&self) -> Vec<Gear> {
+    let mut gears: Vec<Gear> = self
+      .parts
+      .iter()
+      .filter(|(_, p)| p.is_star())
+      .filter_map(|(&pos, _)| match self.adjacent_numbers(pos)[..] {
+        [(_, a), (_, b)] => Some(Gear {
+          pos,
+          parts: (a, b),
+          ratio: a * b,
+        }),
+        _ => None,
+      })
+      .collect();
+
+    gears.sort_by_key(|g| (g.pos.1, g.pos.0));
+    gears
+  }
+
+  /// Return the position and length of every occurrence of the given part
+  /// number, ordered by position.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day3::Engine;
+  ///
+  /// let e: Engine = "12.3\n.*..\n..12".parse().unwrap();
+  ///
+  /// assert_eq!(e.find_number(12), vec![((0, 0), 2), ((2, 2), 2)]);
+  /// assert!(e.find_number(4).is_empty());
+  /// ```
+  pub fn find_number(&self, n: usize) -> Vec<((isize, isize), usize)> {
+    let mut found: Vec<_> = self
+      .parts
+      .iter()
+      .filter(|(_, p)| p.number() == Some(n))
+      .map(|(&pos, p)| (pos, p.len))
+      .collect();
+
+    found.sort_by_key(|&((x, y), _)| (y, x));
+    found
+  }
+
+  /// Return the position and value of every number adjacent to the given
+  /// cell, ordered by position.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day3::Engine;
+  ///
+  /// let e: Engine = "12.3\n.*..\n..45".parse().unwrap();
+  ///
+  /// assert_eq!(e.adjacent_numbers((1, 1)), vec![((0, 0), 12), ((2, 2), 45)]);
+  /// assert_eq!(e.adjacent_numbers((2, 1)), vec![((0, 0), 12), ((3, 0), 3), ((2, 2), 45)]);
+  /// ```
+  pub fn adjacent_numbers(&self, pos: (isize, isize)) -> Vec<((isize, isize), usize)> {
+    self
+      .neighbors(pos, 1)
+      .into_iter()
+      .filter_map(|(pos, p)| p.number().map(|n| (pos, n)))
+      .collect()
+  }
+
+  /// Return the part covering the given cell, along with its starting cell.
+  /// Any cell of a multi-digit number will find that number.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day3::Engine;
+  ///
+  /// let e: Engine = "..123\n.*...".parse().unwrap();
+  ///
+  /// assert_eq!(e.part_at((3, 0)).map(|(pos, p)| (pos, p.number())), Some(((2, 0), Some(123))));
+  /// assert!(e.part_at((1, 1)).is_some_and(|(_, p)| p.is_star()));
+  /// assert!(e.part_at((0, 0)).is_none());
+  /// ```
+  pub fn part_at(&self, cell: (isize, isize)) -> Option<((isize, isize), &Part)> {
+    let start = self.cells.get(&cell)?;
+    self.parts.get(start).map(|p| (*start, p))
+  }
+
+  /// Return each distinct part within the neighbourhood of the `len` cells
+  /// starting at `pos`, ordered by position.
+  pub fn neighbors(&self, pos: (isize, isize), len: usize) -> Vec<((isize, isize), &Part)> {
+    let own: Vec<_> = self.part_cells(pos, len).collect();
+    let offsets: Vec<(isize, isize)> = match self.neighborhood {
+      Neighborhood::Orthogonal => vec![(0, -1), (-1, 0), (1, 0), (0, 1)],
+      Neighborhood::Moore => Self::square(1),
+      Neighborhood::Radius(n) => Self::square(n as isize),
+    };
+
+    let mut found: Vec<_> = own
+      .iter()
+      .flat_map(|&(x, y)| offsets.iter().map(move |&(dx, dy)| (x + dx, y + dy)))
+      .filter(|cell| !own.contains(cell))
+      .filter_map(|cell| self.part_at(cell))
+      .collect();
+
+    found.sort_by_key(|&((x, y), _)| (y, x));
+    found.dedup_by_key(|&mut (pos, _)| pos);
+    found
+  }
+
+  /// Every offset within `n` steps of a cell, excluding the cell itself.
+  fn square(n: isize) -> Vec<(isize, isize)> {
+    (-n..=n)
+      .flat_map(|dy| (-n..=n).map(move |dx| (dx, dy)))
+      .filter(|&d| d != (0, 0))
+      .collect()
+  }
 
   pub fn symbol_adjacent_parts(&self) -> Vec<usize> {
     self
@@ -118,57 +400,114 @@ impl Engine {
       .collect()
   }
 
-  fn adjacent_stars(&self, (x, y): (isize, isize), len: usize) -> Vec<(isize, isize)> {
-    let mut results = vec![];
+  fn adjacent_stars(&self, pos: (isize, isize), len: usize) -> Vec<(isize, isize)> {
+    self
+      .neighbors(pos, len)
+      .into_iter()
+      .filter(|(_, p)| p.is_star())
+      .map(|(pos, _)| pos)
+      .collect()
+  }
 
-    for test_x in (x - 1)..(x + 1 + len as isize) {
-      let above = (test_x, y - 1);
-      let below = (test_x, y + 1);
+  fn is_symbol_adjacent(&self, pos: (isize, isize), len: usize) -> bool {
+    self.neighbors(pos, len).iter().any(|(_, p)| p.is_symbol())
+  }
+}
 
-      if self.parts.get(&above).is_some_and(|part| part.is_star()) {
-        results.push(above);
-      }
-      if self.parts.get(&below).is_some_and(|part| part.is_symbol()) {
-        results.push(below);
+impl Engine {
+  /// Classify the part starting at the given cell.
+  pub fn annotation(&self, pos: (isize, isize)) -> Option<Annotation> {
+    let part = self.parts.get(&pos)?;
+
+    Some(match part.value {
+      PartValue::Number(_) if self.is_symbol_adjacent(pos, part.len) => Annotation::Part,
+      PartValue::Number(_) => Annotation::Loose,
+      PartValue::Symbol('*') if self.adjacent_numbers(pos).len() == 2 => Annotation::Gear,
+      PartValue::Symbol('*') => Annotation::Star,
+      PartValue::Symbol(_) => Annotation::Symbol,
+    })
+  }
+
+  /// Render the schematic in the given mode. Characters which aren't
+  /// symbols under the engine's config are drawn as written, but never
+  /// highlighted or listed in the legend.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day3::{Engine, RenderMode};
+  ///
+  /// let e: Engine = "12.3\n.*..\n..45".parse().unwrap();
+  ///
+  /// assert_eq!(
+  ///   e.render(RenderMode::Legend),
+  ///   "12.3\n.*..\n..45\n\n\
+  ///    part     12 @ (0, 0)\n\
+  ///    loose     3 @ (3, 0)\n\
+  ///    gear      * @ (1, 1) ratio 540\n\
+  ///    part     45 @ (2, 2)\n"
+  /// );
+  /// ```
+  pub fn render(&self, mode: RenderMode) -> String {
+    let mut out = String::new();
+
+    let width = self.size.0;
+
+    for y in 0..self.size.1 {
+      for x in 0..width {
+        let Some(((sx, sy), part)) = self.part_at((x, y)) else {
+          out.push(*self.ignored.get(&(x, y)).unwrap_or(&self.blank));
+          continue;
+        };
+        // a wrapped number continues on the following line, so find this
+        // cell's digit by its linear distance from the start of the part.
+        let offset = ((y - sy) * width + x - sx) as usize;
+        let text = part.to_string().chars().nth(offset).unwrap().to_string();
+        let text = match (mode, self.annotation((sx, sy))) {
+          (RenderMode::Legend, _) => text,
+          (_, Some(Annotation::Part)) => text.green().to_string(),
+          (_, Some(Annotation::Loose)) => text.red().to_string(),
+          (_, Some(Annotation::Gear)) => text.yellow().bold().to_string(),
+          (_, Some(Annotation::Star)) => text.blue().to_string(),
+          _ => text,
+        };
+        out.push_str(&text);
       }
+      out.push('\n');
     }
 
-    let before = (x - 1, y);
-    let after = (x + len as isize, y);
-
-    if self.parts.get(&before).is_some_and(|part| part.is_symbol()) {
-      results.push(before);
-    }
-    if self.parts.get(&after).is_some_and(|part| part.is_symbol()) {
-      results.push(after);
+    if mode == RenderMode::Legend {
+      let mut positions: Vec<_> = self.parts.keys().copied().collect();
+      positions.sort_by_key(|&(x, y)| (y, x));
+      out.push('\n');
+
+      for pos in positions {
+        let part = &self.parts[&pos];
+        let annotation = self.annotation(pos).unwrap();
+        out.push_str(&format!("{:<6} {:>4} @ {:?}", annotation, part, pos));
+        if annotation == Annotation::Gear {
+          let ratio: usize = self.adjacent_numbers(pos).iter().map(|(_, n)| n).product();
+          out.push_str(&format!(" ratio {}", ratio));
+        }
+        out.push('\n');
+      }
     }
 
-    results
+    out
   }
+}
 
-  fn is_symbol_adjacent(&self, (x, y): (isize, isize), len: usize) -> bool {
-    for test_x in (x - 1)..(x + 1 + len as isize) {
-      let above = (test_x, y - 1);
-      let below = (test_x, y + 1);
-
-      if self.parts.get(&above).is_some_and(|part| part.is_symbol()) {
-        return true;
-      }
-      if self.parts.get(&below).is_some_and(|part| part.is_symbol()) {
-        return true;
-      }
+impl Display for Part {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.value {
+      PartValue::Number(n) => f.pad(&format!("{:0width$}", n, width = self.len)),
+      PartValue::Symbol(ch) => f.pad(&ch.to_string()),
     }
+  }
+}
 
-    let before = (x - 1, y);
-    let after = (x + len as isize, y);
-
-    if self.parts.get(&before).is_some_and(|part| part.is_symbol()) {
-      return true;
-    }
-    if self.parts.get(&after).is_some_and(|part| part.is_symbol()) {
-      return true;
-    }
-    false
+impl Display for Engine {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.render(RenderMode::Color))
   }
 }
 
@@ -206,28 +545,7 @@ mod tests {
   #[test]
   fn part2_example() {
     let e: Engine = EXAMPLE_INPUT.parse().expect("Failed to parse!");
-    let mut m: HashMap<(isize, isize), Vec<(isize, isize)>> = HashMap::new();
-
-    e.star_adjacent_parts().iter().for_each(|(&pos, stars)| {
-      stars.iter().for_each(|&star_pos| {
-        m.entry(star_pos)
-          .and_modify(|list| list.push(pos))
-          .or_insert_with(|| vec![pos]);
-      });
-    });
-
-    let s = m
-      .iter()
-      .filter(|(_star_pos, parts)| parts.len() == 2)
-      .map(|(_, parts)| {
-        if let PartValue::Number(v1) = e.parts.get(&parts[0]).unwrap().value {
-          if let PartValue::Number(v2) = e.parts.get(&parts[1]).unwrap().value {
-            return v1 * v2;
-          }
-        }
-        panic!("shit went bad");
-      })
-      .sum::<usize>();
+    let s: usize = e.gears().iter().map(|g| g.ratio).sum();
 
     assert_eq!(s, 467835);
   }
@@ -235,29 +553,56 @@ mod tests {
   #[test]
   fn part2_solution() {
     let e: Engine = INPUT.parse().expect("Failed to parse!");
-    let mut m: HashMap<(isize, isize), Vec<(isize, isize)>> = HashMap::new();
+    let s: usize = e.gears().iter().map(|g| g.ratio).sum();
 
-    e.star_adjacent_parts().iter().for_each(|(&pos, stars)| {
-      stars.iter().for_each(|&star_pos| {
-        m.entry(star_pos)
-          .and_modify(|list| list.push(pos))
-          .or_insert_with(|| vec![pos]);
-      });
-    });
+    assert_eq!(s, 69527306);
+  }
 
-    let s = m
-      .iter()
-      .filter(|(_star_pos, parts)| parts.len() == 2)
-      .map(|(_, parts)| {
-        if let PartValue::Number(v1) = e.parts.get(&parts[0]).unwrap().value {
-          if let PartValue::Number(v2) = e.parts.get(&parts[1]).unwrap().value {
-            return v1 * v2;
-          }
-        }
-        panic!("shit went bad");
-      })
-      .sum::<usize>();
+  #[test]
+  fn star_adjacency_ignores_other_symbols() {
+    let e: Engine = "12.\n..#\n*..".parse().expect("Failed to parse!");
 
-    assert_eq!(s, 69527306);
+    assert!(e.star_adjacent_parts().is_empty());
+    assert_eq!(e.symbol_adjacent_parts(), vec![12]);
+  }
+
+  #[test]
+  fn wrapped_numbers() {
+    let config = EngineConfig {
+      wrap_numbers: true,
+      ..Default::default()
+    };
+    let e = Engine::parse_with("..12\n3*..\n....", &config).expect("Failed to parse!");
+
+    assert_eq!(e.find_number(123), vec![((2, 0), 3)]);
+    assert_eq!(e.part_at((0, 1)).map(|(pos, _)| pos), Some((2, 0)));
+    assert_eq!(e.symbol_adjacent_parts(), vec![123]);
+    assert_eq!(e.render(RenderMode::Legend).lines().next(), Some("..12"));
+  }
+
+  #[test]
+  fn symbol_classes_and_radius() {
+    let config = EngineConfig {
+      symbols: Some(vec!['#']),
+      neighborhood: Neighborhood::Radius(2),
+      ..Default::default()
+    };
+    let e = Engine::parse_with("1....\n..$..\n....#\n..7..", &config).expect("Failed to parse!");
+
+    assert!(e.part_at((2, 1)).is_none());
+    assert_eq!(e.symbol_adjacent_parts(), vec![7]);
+    assert!(e
+      .render(RenderMode::Legend)
+      .starts_with("1....\n..$..\n....#\n..7..\n"));
+
+    let config = EngineConfig {
+      symbols: Some(vec!['#']),
+      ..Default::default()
+    };
+    let e = Engine::parse_with("1$.\n..#", &config).expect("Failed to parse!");
+    assert_eq!(
+      e.render(RenderMode::Legend),
+      "1$.\n..#\n\nloose     1 @ (0, 0)\nsymbol    # @ (2, 1)\n"
+    );
   }
 }
@@ -1,11 +1,9 @@
-use std::{
-  collections::{HashMap, HashSet},
-  str::FromStr,
-};
+use std::{collections::HashSet, error::Error, fmt::Display, ops::RangeInclusive, str::FromStr};
 
 const INPUT: &'static str = include_str!("../inputs/day4.txt");
 
 pub struct Card {
+  id: usize,
   winners: Vec<usize>,
   numbers: HashSet<usize>,
 }
@@ -14,11 +12,9 @@ impl FromStr for Card {
   type Err = &'static str;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    let mut parts = s.split(&[':', '|']).skip(1);
+    let (id, winners, numbers) = Card::split(s)?;
 
-    let winners = parts
-      .next()
-      .ok_or("No winning numbers found")?
+    let winners: Vec<usize> = winners
       .split_whitespace()
       .map(|num| {
         num
@@ -27,9 +23,7 @@ impl FromStr for Card {
       })
       .collect::<Result<_, _>>()?;
 
-    let numbers = parts
-      .next()
-      .ok_or("No card numbers found")?
+    let numbers: Vec<usize> = numbers
       .split_whitespace()
       .map(|num| {
         num
@@ -38,50 +32,238 @@ impl FromStr for Card {
       })
       .collect::<Result<_, _>>()?;
 
-    Ok(Self { winners, numbers })
+    Ok(Card::build(id, winners, numbers))
   }
 }
 
+/// Something suspicious found while validating a card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnomalyKind {
+  DuplicateWinner(usize),
+  DuplicateNumber(usize),
+  OutOfRange(usize),
+  Malformed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+  pub card: usize,
+  pub kind: AnomalyKind,
+}
+
+impl Display for Anomaly {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Card {}: ", self.card)?;
+    match &self.kind {
+      AnomalyKind::DuplicateWinner(n) => write!(f, "duplicate winning number {} (counted once)", n),
+      AnomalyKind::DuplicateNumber(n) => write!(f, "duplicate card number {}", n),
+      AnomalyKind::OutOfRange(n) => write!(f, "number {} out of range", n),
+      AnomalyKind::Malformed(s) => write!(f, "malformed number {:?}", s),
+    }
+  }
+}
+
+impl Error for Anomaly {}
+
+/// How strictly to check cards when parsing with [`Card::parse_checked`].
+#[derive(Debug, Clone)]
+pub struct Validation {
+  /// Reject a card on its first anomaly, rather than reporting it as a warning.
+  pub strict: bool,
+  /// The range every winning and card number must fall within.
+  pub range: RangeInclusive<usize>,
+}
+
+impl Validation {
+  pub fn strict() -> Self {
+    Self {
+      strict: true,
+      range: 1..=99,
+    }
+  }
+
+  pub fn lenient() -> Self {
+    Self {
+      strict: false,
+      range: 1..=99,
+    }
+  }
+}
+
+/// The outcome of playing out a pile of scratchcards.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CopyReport {
+  /// How many instances of each card we end up with, in input order.
+  pub counts: Vec<usize>,
+  pub total: usize,
+  /// The id of the card which won the most copies of other cards, and how
+  /// many it won. `None` if no card won anything.
+  pub top: Option<(usize, usize)>,
+}
+
 impl Card {
   pub fn load_all(s: &'static str) -> Result<Vec<Card>, &'static str> {
     s.lines().map(|line| line.parse()).collect()
   }
 
-  pub fn run_p2(cards: Vec<Card>) -> usize {
-    let r = 0..(cards.len());
-    let mut total = 0;
+  /// Split a card into its id, winning numbers and card numbers.
+  fn split(s: &str) -> Result<(usize, &str, &str), &'static str> {
+    let (label, rest) = s.split_once(':').ok_or("No colon found")?;
+    let id = label
+      .split_whitespace()
+      .nth(1)
+      .ok_or("No card id found")?
+      .parse()
+      .map_err(|_| "Failed to parse card id")?;
+    let (winners, numbers) = rest.split_once('|').ok_or("No card numbers found")?;
 
-    // map of card number => number of instances of that card.
-    // initially we have 1 of all the supplied cards
-    let mut counts: HashMap<usize, usize> = r.map(|index| (index, 1)).collect();
+    Ok((id, winners, numbers))
+  }
 
-    while !counts.is_empty() {
-      for index in 0..(cards.len()) {
-        let card = cards.get(index).unwrap();
-        let count = *counts.get(&index).unwrap_or(&0);
+  /// Parse a card, checking for duplicate, out of range or malformed numbers.
+  ///
+  /// In strict mode the first anomaly is returned as an error. Otherwise the
+  /// card is returned along with every anomaly found; malformed numbers are
+  /// skipped and duplicates only count once.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day4::{AnomalyKind, Card, Validation};
+  ///
+  /// let line = "Card 7: 1 2 2 | 3 100 3";
+  ///
+  /// let (card, warnings) = Card::parse_checked(line, &Validation::lenient()).unwrap();
+  /// let kinds: Vec<_> = warnings.into_iter().map(|w| w.kind).collect();
+  ///
+  /// assert_eq!(card.id(), 7);
+  /// assert_eq!(kinds, vec![
+  ///   AnomalyKind::DuplicateWinner(2),
+  ///   AnomalyKind::OutOfRange(100),
+  ///   AnomalyKind::DuplicateNumber(3),
+  /// ]);
+  ///
+  /// let err = Card::parse_checked(line, &Validation::strict()).err().unwrap();
+  /// assert_eq!(err.to_string(), "Card 7: duplicate winning number 2 (counted once)");
+  /// ```
+  pub fn parse_checked(s: &str, validation: &Validation) -> anyhow::Result<(Card, Vec<Anomaly>)> {
+    let (id, winners, numbers) = Card::split(s).map_err(anyhow::Error::msg)?;
+    let mut anomalies = vec![];
 
-        counts.remove(&index);
-        total += count;
+    let winners = Card::check_numbers(winners, validation, &mut anomalies, |n| {
+      AnomalyKind::DuplicateWinner(n)
+    });
+    let numbers = Card::check_numbers(numbers, validation, &mut anomalies, |n| {
+      AnomalyKind::DuplicateNumber(n)
+    });
 
-        // if we don't have any of this card, ignore
-        if count == 0 {
-          continue;
-        }
+    let anomalies: Vec<Anomaly> = anomalies
+      .into_iter()
+      .map(|kind| Anomaly { card: id, kind })
+      .collect();
+
+    if let Some(anomaly) = anomalies.first().filter(|_| validation.strict) {
+      return Err(anomaly.clone().into());
+    }
 
-        // tally these cards and remove them from processing
-        let wins = card.wins();
+    Ok((Card::build(id, winners, numbers), anomalies))
+  }
+
+  /// Parse every line with [`Card::parse_checked`].
+  pub fn load_checked(
+    s: &str,
+    validation: &Validation,
+  ) -> anyhow::Result<Vec<(Card, Vec<Anomaly>)>> {
+    s.lines()
+      .map(|line| Card::parse_checked(line, validation))
+      .collect()
+  }
 
-        if wins != 0 {
-          // add new cards for each instance of the card that won
-          let range = (index + 1)..(index + 1 + wins);
-          range.for_each(|i| {
-            counts.entry(i).and_modify(|v| *v += count).or_insert(count);
-          });
+  fn check_numbers(
+    s: &str,
+    validation: &Validation,
+    anomalies: &mut Vec<AnomalyKind>,
+    duplicate: impl Fn(usize) -> AnomalyKind,
+  ) -> Vec<usize> {
+    let mut seen = HashSet::new();
+
+    s.split_whitespace()
+      .filter_map(|token| match token.parse::<usize>() {
+        Ok(n) => {
+          if !validation.range.contains(&n) {
+            anomalies.push(AnomalyKind::OutOfRange(n));
+          }
+          if !seen.insert(n) {
+            anomalies.push(duplicate(n));
+          }
+          Some(n)
+        }
+        Err(_) => {
+          anomalies.push(AnomalyKind::Malformed(token.to_string()));
+          None
         }
+      })
+      .collect()
+  }
+
+  // duplicate winners are dropped, so a number can't win twice.
+  fn build(id: usize, winners: Vec<usize>, numbers: Vec<usize>) -> Card {
+    let mut seen = HashSet::new();
+
+    Card {
+      id,
+      winners: winners.into_iter().filter(|&n| seen.insert(n)).collect(),
+      numbers: numbers.into_iter().collect(),
+    }
+  }
+
+  pub fn run_p2(cards: Vec<Card>) -> usize {
+    Card::copies(&cards).total
+  }
+
+  /// Play out the winning copies of every card in a single forward pass.
+  ///
+  /// A card can only win copies of the cards after it, so once we reach a
+  /// card its count is final and can be pushed onto the cards it wins.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day4::Card;
+  ///
+  /// let cards = Card::load_all("Card 1: 1 2 | 1 2\nCard 2: 3 | 3\nCard 3: 4 | 5").unwrap();
+  /// let report = Card::copies(&cards);
+  ///
+  /// assert_eq!(report.counts, vec![1, 2, 4]);
+  /// assert_eq!(report.total, 7);
+  /// assert_eq!(report.top, Some((1, 2)));
+  /// ```
+  pub fn copies(cards: &[Card]) -> CopyReport {
+    let mut counts = vec![1; cards.len()];
+    let mut top: Option<(usize, usize)> = None;
+
+    for (index, card) in cards.iter().enumerate() {
+      let count = counts[index];
+      let wins = card.wins();
+      let end = (index + 1 + wins).min(cards.len());
+
+      counts[(index + 1)..end]
+        .iter_mut()
+        .for_each(|c| *c += count);
+
+      let contributed = count * (end - index - 1);
+      if contributed > 0 && top.is_none_or(|(_, best)| contributed > best) {
+        top = Some((card.id, contributed));
       }
     }
 
-    total
+    CopyReport {
+      total: counts.iter().sum(),
+      counts,
+      top,
+    }
+  }
+
+  pub fn id(&self) -> usize {
+    self.id
   }
 
   pub fn wins(&self) -> usize {
@@ -138,4 +320,38 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
     let ans = Card::run_p2(cards);
     assert_eq!(ans, 5539496);
   }
+
+  #[test]
+  fn copies_example() {
+    let cards = Card::load_all(EXAMPLE_INPUT).expect("Failed to load cards");
+    let report = Card::copies(&cards);
+
+    assert_eq!(report.counts, vec![1, 2, 4, 8, 14, 1]);
+    assert_eq!(report.top, Some((3, 8)));
+    assert_eq!(cards[3].id(), 4);
+  }
+
+  #[test]
+  fn validation_solution() {
+    let cards = Card::load_checked(INPUT, &Validation::strict()).expect("Invalid cards");
+    assert!(cards.iter().all(|(_, warnings)| warnings.is_empty()));
+
+    let cards = Card::load_checked("Card 1: 1 x | 2\nCard 2: 3 | 3 4", &Validation::lenient())
+      .expect("Failed to load cards");
+    assert_eq!(
+      cards[0].1,
+      vec![Anomaly {
+        card: 1,
+        kind: AnomalyKind::Malformed("x".to_string())
+      }]
+    );
+    assert_eq!(cards[1].0.wins(), 1);
+
+    // a repeated winning number still only wins once
+    let card: Card = "Card 1: 5 5 | 5".parse().expect("Failed to parse card");
+    assert_eq!(card.wins(), 1);
+    let (card, warnings) =
+      Card::parse_checked("Card 1: 5 5 | 5", &Validation::lenient()).expect("Failed to parse card");
+    assert_eq!((card.wins(), warnings.len()), (1, 1));
+  }
 }
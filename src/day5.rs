@@ -1,9 +1,12 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
 use std::ops::Range;
 use std::str::FromStr;
 
 const INPUT: &'static str = include_str!("../inputs/day5.txt");
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct ConversionRange {
   src_start: usize,
   dest_start: usize,
@@ -55,14 +58,298 @@ impl ConversionRange {
   pub fn convert(&self, n: usize) -> usize {
     self.dest_start + (n - self.src_start)
   }
+
+  /// The range of source values this conversion applies to.
+  pub fn src(&self) -> Range<usize> {
+    self.src_start..(self.src_start + self.len)
+  }
+
+  /// Split `r` on the boundaries of this conversion, returning the converted
+  /// part of `r` (if any) and the parts which fall outside of it.
+  pub fn convert_range(&self, r: Range<usize>) -> (Option<Range<usize>>, Vec<Range<usize>>) {
+    let src = self.src();
+    let start = r.start.max(src.start);
+    let end = r.end.min(src.end);
+
+    if start >= end {
+      return (None, vec![r]);
+    }
+
+    let before = r.start..start;
+    let after = end..r.end;
+    let rest = [before, after]
+      .into_iter()
+      .filter(|r| !r.is_empty())
+      .collect();
+
+    (Some(self.convert(start)..(self.convert(end - 1) + 1)), rest)
+  }
+}
+
+impl Display for ConversionRange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {} {}", self.dest_start, self.src_start, self.len)
+  }
+}
+
+/// A single mapping built from one or more almanac stages. Pieces are sorted
+/// by source, never overlap and never map a value to itself; anything not
+/// covered by a piece maps to itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PiecewiseMap {
+  pieces: Vec<ConversionRange>,
+}
+
+impl PiecewiseMap {
+  /// Build a map from the conversions of a single stage. Where conversions
+  /// overlap the first one listed wins, as in `Almanac::convert_stage`.
+  pub fn from_stage(conversions: &[ConversionRange]) -> Self {
+    let mut claimed: Vec<Range<usize>> = vec![];
+    let mut pieces = vec![];
+
+    for c in conversions {
+      let mut free = vec![c.src()];
+
+      for taken in &claimed {
+        free = free
+          .into_iter()
+          .flat_map(|r| {
+            let (start, end) = (taken.start.max(r.start), taken.end.min(r.end));
+            if start >= end {
+              vec![r]
+            } else {
+              vec![r.start..start, end..r.end]
+            }
+          })
+          .filter(|r| !r.is_empty())
+          .collect();
+      }
+
+      pieces.extend(free.into_iter().map(|r| ConversionRange {
+        src_start: r.start,
+        dest_start: c.convert(r.start),
+        len: r.len(),
+      }));
+      claimed.push(c.src());
+    }
+
+    Self::normalized(pieces)
+  }
+
+  /// Sort non-overlapping pieces, dropping empty and identity pieces and
+  /// merging neighbours which continue the same shift.
+  fn normalized(mut pieces: Vec<ConversionRange>) -> Self {
+    pieces.retain(|p| p.len > 0 && p.src_start != p.dest_start);
+    pieces.sort_by_key(|p| p.src_start);
+
+    let mut merged: Vec<ConversionRange> = Vec::with_capacity(pieces.len());
+
+    for p in pieces {
+      match merged.last_mut() {
+        Some(last)
+          if last.src_start + last.len == p.src_start
+            && last.dest_start + last.len == p.dest_start =>
+        {
+          last.len += p.len
+        }
+        _ => merged.push(p),
+      }
+    }
+
+    Self { pieces: merged }
+  }
+
+  pub fn pieces(&self) -> &[ConversionRange] {
+    &self.pieces
+  }
+
+  /// Map a single value.
+  pub fn lookup(&self, n: usize) -> usize {
+    let index = self.pieces.partition_point(|p| p.src_start <= n);
+
+    match index.checked_sub(1).map(|i| &self.pieces[i]) {
+      Some(p) if p.includes(n) => p.convert(n),
+      _ => n,
+    }
+  }
+
+  /// Split `r` into consecutive sub-ranges, each paired with the value its
+  /// first element maps to. Gaps between pieces are included as identity.
+  fn split(&self, r: Range<usize>) -> Vec<(Range<usize>, usize)> {
+    let first = self
+      .pieces
+      .partition_point(|p| p.src_start + p.len <= r.start);
+    let mut result = vec![];
+    let mut at = r.start;
+
+    for p in self.pieces[first..]
+      .iter()
+      .take_while(|p| p.src_start < r.end)
+    {
+      if at < p.src_start {
+        result.push((at..p.src_start, at));
+      }
+      let end = r.end.min(p.src_start + p.len);
+      let start = at.max(p.src_start);
+      result.push((start..end, p.convert(start)));
+      at = end;
+    }
+
+    if at < r.end {
+      result.push((at..r.end, at));
+    }
+
+    result
+  }
+
+  /// Return every range of values which map into `r`.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::{ConversionRange, PiecewiseMap};
+  ///
+  /// let c: ConversionRange = "10 0 5".parse().unwrap();
+  /// let m = PiecewiseMap::from_stage(&[c]);
+  ///
+  /// assert_eq!(m.preimage(3..12), vec![0..2, 5..12]);
+  /// assert_eq!(m.preimage(100..101), vec![100..101]);
+  /// ```
+  pub fn preimage(&self, r: Range<usize>) -> Vec<Range<usize>> {
+    let mut result = vec![];
+
+    for (segment, mapped) in self.split(0..usize::MAX) {
+      let start = r.start.max(mapped);
+      let end = r.end.min(mapped + segment.len());
+
+      if start < end {
+        let src_start = segment.start + (start - mapped);
+        result.push(src_start..(src_start + (end - start)));
+      }
+    }
+
+    normalize(result)
+  }
+
+  /// Return the map equivalent to applying `self` and then `next`.
+  pub fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+    let mut pieces = vec![];
+
+    for (segment, mapped) in self.split(0..usize::MAX) {
+      for (sub, dest_start) in next.split(mapped..(mapped + segment.len())) {
+        pieces.push(ConversionRange {
+          src_start: segment.start + (sub.start - mapped),
+          dest_start,
+          len: sub.len(),
+        });
+      }
+    }
+
+    Self::normalized(pieces)
+  }
+}
+
+impl Display for PiecewiseMap {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for p in &self.pieces {
+      writeln!(f, "{}", p)?;
+    }
+    Ok(())
+  }
+}
+
+/// Sort the given ranges and merge any which overlap or touch, dropping
+/// empty ranges.
+pub fn normalize(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+  ranges.retain(|r| !r.is_empty());
+  ranges.sort_by_key(|r| r.start);
+
+  let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+
+  for r in ranges {
+    match merged.last_mut() {
+      Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+      _ => merged.push(r),
+    }
+  }
+
+  merged
 }
 
 #[derive(Debug)]
 pub struct Almanac {
   seeds: Vec<usize>,
   pub conversions: Vec<Vec<ConversionRange>>,
+  /// The source and destination category of each stage in `conversions`.
+  pub categories: Vec<(String, String)>,
+  /// The line each conversion was parsed from, for reporting problems.
+  line_numbers: Vec<Vec<usize>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+  /// The source range overlaps the one on the given line, which was listed
+  /// first and so takes priority.
+  Overlap(usize),
+  /// The source or destination range runs past `usize::MAX`.
+  Overflow,
+  Empty,
+}
+
+/// A problem with a single conversion, found by [`Almanac::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+  pub stage: String,
+  pub line: usize,
+  pub problem: Problem,
+}
+
+impl Display for Issue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "line {} ({}): ", self.line, self.stage)?;
+    match self.problem {
+      Problem::Overlap(other) => write!(f, "overlaps line {}", other),
+      Problem::Overflow => write!(f, "range overflows"),
+      Problem::Empty => write!(f, "range is empty"),
+    }
+  }
+}
+
+/// The parts of one stage's input which no conversion covers, and which
+/// therefore map to themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+  pub stage: String,
+  pub identity: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CategoryError {
+  /// No stage converts from or to this category.
+  Unknown(String),
+  NoPath(String, String),
+  /// More than one chain of stages leads between the two categories.
+  Ambiguous(String, String),
+  /// A stage which isn't on the chain from seed to location.
+  Unused(String),
+}
+
+impl Display for CategoryError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CategoryError::Unknown(c) => write!(f, "Unknown category: {}", c),
+      CategoryError::NoPath(from, to) => write!(f, "No conversion from {} to {}", from, to),
+      CategoryError::Ambiguous(from, to) => {
+        write!(f, "More than one conversion from {} to {}", from, to)
+      }
+      CategoryError::Unused(stage) => {
+        write!(f, "Stage {} isn't on the seed to location chain", stage)
+      }
+    }
+  }
+}
+
+impl Error for CategoryError {}
+
 impl Almanac {
   pub fn part2_seed_ranges(&self) -> Vec<Range<usize>> {
     let mut i = self.seeds.iter();
@@ -81,10 +368,303 @@ impl Almanac {
   }
 
   pub fn seed_to_location(&self, seed: usize) -> usize {
-    (0..self.conversions.len()).fold(seed, |value, index| self.convert(value, index))
+    (0..self.conversions.len()).fold(seed, |value, index| self.convert_stage(value, index))
+  }
+
+  /// Map a set of seed ranges through every stage, returning the minimal set
+  /// of location ranges they cover.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::Almanac;
+  ///
+  /// let a: Almanac = "seeds: 1 10\n\nseed-to-location map:\n100 5 2".parse().unwrap();
+  ///
+  /// assert_eq!(a.seed_ranges_to_locations(a.part2_seed_ranges()), vec![1..5, 7..11, 100..102]);
+  /// ```
+  pub fn seed_ranges_to_locations(&self, ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    (0..self.conversions.len()).fold(normalize(ranges), |ranges, index| {
+      self.convert_ranges(ranges, index)
+    })
+  }
+
+  /// Map a set of ranges through a single stage. As with `convert_stage`, where
+  /// conversions overlap the first one listed wins.
+  pub fn convert_ranges(&self, ranges: Vec<Range<usize>>, index: usize) -> Vec<Range<usize>> {
+    let mut pending = ranges;
+    let mut converted = vec![];
+
+    for conversion in &self.conversions[index] {
+      let mut unmatched = vec![];
+
+      for r in pending {
+        let (hit, rest) = conversion.convert_range(r);
+        converted.extend(hit);
+        unmatched.extend(rest);
+      }
+
+      pending = unmatched;
+    }
+
+    converted.extend(pending);
+    normalize(converted)
   }
 
-  pub fn convert(&self, value: usize, index: usize) -> usize {
+  /// Compose the given stages into a single map. `compose(0..n)` maps seeds
+  /// directly to locations, where `n` is the number of stages.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::Almanac;
+  ///
+  /// let a: Almanac = "seeds: 1\n\nseed-to-soil map:\n10 0 5\n\nsoil-to-location map:\n0 12 2".parse().unwrap();
+  /// let m = a.compose(0..2);
+  ///
+  /// assert_eq!(m.to_string(), "10 0 2\n0 2 2\n14 4 1\n0 12 2\n");
+  /// assert_eq!(m.lookup(3), 1);
+  /// assert_eq!(m.lookup(12), 0);
+  /// assert_eq!(m.lookup(20), 20);
+  /// ```
+  pub fn compose(&self, stages: Range<usize>) -> PiecewiseMap {
+    self.conversions[stages]
+      .iter()
+      .fold(PiecewiseMap::default(), |map, stage| {
+        map.then(&PiecewiseMap::from_stage(stage))
+      })
+  }
+
+  /// Walk a location range back through every stage. The result has one
+  /// entry per stage input plus the location range itself, so `[0]` holds
+  /// the seed ranges and the last entry holds `[locations]`.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::Almanac;
+  ///
+  /// let a: Almanac = "seeds: 1\n\nseed-to-soil map:\n10 0 5\n\nsoil-to-location map:\n0 12 2".parse().unwrap();
+  /// let p = a.preimages(0..1);
+  ///
+  /// assert_eq!(p, vec![vec![2..3, 12..13], vec![0..1, 12..13], vec![0..1]]);
+  /// ```
+  pub fn preimages(&self, locations: Range<usize>) -> Vec<Vec<Range<usize>>> {
+    let mut result = vec![vec![locations]];
+
+    for stage in self.conversions.iter().rev() {
+      let map = PiecewiseMap::from_stage(stage);
+      let ranges = result
+        .last()
+        .unwrap()
+        .iter()
+        .flat_map(|r| map.preimage(r.clone()))
+        .collect();
+      result.push(normalize(ranges));
+    }
+
+    result.reverse();
+    result
+  }
+
+  /// Return every seed range which maps into the given location range.
+  pub fn locations_to_seeds(&self, locations: Range<usize>) -> Vec<Range<usize>> {
+    self.preimages(locations).swap_remove(0)
+  }
+
+  /// Return the stages which convert `from` into `to`, in the order they
+  /// should be applied.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::{Almanac, CategoryError};
+  ///
+  /// let a: Almanac = "seeds: 1\n\nsoil-to-location map:\n0 12 2\n\nseed-to-soil map:\n10 0 5".parse().unwrap();
+  ///
+  /// // the stages are put in seed to location order when parsing
+  /// assert_eq!(a.path("seed", "location"), Ok(vec![0, 1]));
+  /// assert_eq!(a.path("soil", "location"), Ok(vec![1]));
+  /// assert_eq!(
+  ///   a.path("location", "seed"),
+  ///   Err(CategoryError::NoPath("location".into(), "seed".into()))
+  /// );
+  /// assert_eq!(a.path("seed", "z"), Err(CategoryError::Unknown("z".into())));
+  /// ```
+  pub fn path(&self, from: &str, to: &str) -> Result<Vec<usize>, CategoryError> {
+    for c in [from, to] {
+      if !self.categories.iter().any(|(f, t)| f == c || t == c) {
+        return Err(CategoryError::Unknown(c.to_string()));
+      }
+    }
+
+    let mut edges: HashMap<&str, Vec<(usize, &str)>> = HashMap::new();
+    for (index, (f, t)) in self.categories.iter().enumerate() {
+      edges.entry(f).or_default().push((index, t));
+    }
+
+    // the graph is tiny, so just find every simple path between the two.
+    let mut found: Vec<Vec<usize>> = vec![];
+    let mut stack = vec![(from, vec![], vec![from])];
+
+    while let Some((at, stages, visited)) = stack.pop() {
+      if at == to {
+        found.push(stages);
+        continue;
+      }
+
+      for &(index, next) in edges.get(at).into_iter().flatten() {
+        if !visited.contains(&next) {
+          let mut stages = stages.clone();
+          let mut visited = visited.clone();
+          stages.push(index);
+          visited.push(next);
+          stack.push((next, stages, visited));
+        }
+      }
+    }
+
+    match found.len() {
+      0 => Err(CategoryError::NoPath(from.to_string(), to.to_string())),
+      1 => Ok(found.remove(0)),
+      _ => Err(CategoryError::Ambiguous(from.to_string(), to.to_string())),
+    }
+  }
+
+  /// Convert a value from one named category to another.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::Almanac;
+  ///
+  /// let a: Almanac = "seeds: 1\n\nsoil-to-location map:\n0 12 2\n\nseed-to-soil map:\n10 0 5".parse().unwrap();
+  ///
+  /// assert_eq!(a.convert("seed", "location", 2), Ok(0));
+  /// assert_eq!(a.convert("soil", "location", 2), Ok(2));
+  /// assert!(a.convert("location", "seed", 2).is_err());
+  /// ```
+  pub fn convert(&self, from: &str, to: &str, value: usize) -> Result<usize, CategoryError> {
+    Ok(
+      self
+        .path(from, to)?
+        .into_iter()
+        .fold(value, |v, index| self.convert_stage(v, index)),
+    )
+  }
+
+  /// The name of a stage, as written in its header, e.g. `seed-to-soil`.
+  pub fn stage_name(&self, index: usize) -> String {
+    let (from, to) = &self.categories[index];
+    format!("{}-to-{}", from, to)
+  }
+
+  /// Check every stage for overlapping, overflowing or empty conversions.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::{Almanac, Problem};
+  ///
+  /// let a: Almanac = "seeds: 1\n\nseed-to-location map:\n10 0 5\n20 3 5\n0 9 0".parse().unwrap();
+  /// let problems: Vec<_> = a.validate().into_iter().map(|i| (i.line, i.problem)).collect();
+  ///
+  /// assert_eq!(problems, vec![(5, Problem::Overlap(4)), (6, Problem::Empty)]);
+  /// assert_eq!(a.validate()[0].to_string(), "line 5 (seed-to-location): overlaps line 4");
+  /// ```
+  pub fn validate(&self) -> Vec<Issue> {
+    let mut issues = vec![];
+
+    for (index, stage) in self.conversions.iter().enumerate() {
+      let lines = &self.line_numbers[index];
+      let mut issue = |i: usize, problem| {
+        issues.push(Issue {
+          stage: self.stage_name(index),
+          line: lines[i],
+          problem,
+        })
+      };
+
+      for (i, c) in stage.iter().enumerate() {
+        if c.src_start.checked_add(c.len).is_none() || c.dest_start.checked_add(c.len).is_none() {
+          issue(i, Problem::Overflow);
+          continue;
+        }
+        if c.len == 0 {
+          issue(i, Problem::Empty);
+          continue;
+        }
+
+        let src = c.src();
+        let earlier = stage[..i].iter().position(|o| {
+          o.len > 0
+            && o.src_start.checked_add(o.len).is_some()
+            && o.src_start < src.end
+            && src.start < o.src_start + o.len
+        });
+        if let Some(other) = earlier {
+          issue(i, Problem::Overlap(lines[other]));
+        }
+      }
+    }
+
+    issues
+  }
+
+  /// Push the given seed ranges through every stage, reporting which parts
+  /// of each stage's input fall through to the identity mapping.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::Almanac;
+  ///
+  /// let a: Almanac = "seeds: 0 10\n\nseed-to-soil map:\n10 0 5\n\nsoil-to-location map:\n0 12 2".parse().unwrap();
+  /// let coverage = a.coverage(a.part2_seed_ranges());
+  ///
+  /// assert_eq!(coverage[0].stage, "seed-to-soil");
+  /// assert_eq!(coverage[0].identity, vec![5..10]);
+  /// assert_eq!(coverage[1].identity, vec![5..12, 14..15]);
+  /// ```
+  pub fn coverage(&self, seeds: Vec<Range<usize>>) -> Vec<Coverage> {
+    let mut ranges = normalize(seeds);
+    let mut result = vec![];
+
+    for index in 0..self.conversions.len() {
+      let identity = self.conversions[index]
+        .iter()
+        .fold(ranges.clone(), |pending, c| {
+          pending
+            .into_iter()
+            .flat_map(|r| c.convert_range(r).1)
+            .collect()
+        });
+
+      result.push(Coverage {
+        stage: self.stage_name(index),
+        identity: normalize(identity),
+      });
+      ranges = self.convert_ranges(ranges, index);
+    }
+
+    result
+  }
+
+  /// Return the value at each step of converting `seed` to a location,
+  /// starting with the seed itself.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day5::Almanac;
+  ///
+  /// let a: Almanac = "seeds: 1\n\nseed-to-soil map:\n10 0 5\n\nsoil-to-location map:\n0 12 2".parse().unwrap();
+  ///
+  /// assert_eq!(a.explain(2), vec![2, 12, 0]);
+  /// ```
+  pub fn explain(&self, seed: usize) -> Vec<usize> {
+    let mut chain = vec![seed];
+
+    for index in 0..self.conversions.len() {
+      chain.push(self.convert_stage(*chain.last().unwrap(), index));
+    }
+
+    chain
+  }
+
+  pub fn convert_stage(&self, value: usize, index: usize) -> usize {
     let ranges = &self.conversions[index];
 
     if let Some(range) = ranges.iter().find(|r| r.includes(value)) {
@@ -96,16 +676,17 @@ impl Almanac {
 }
 
 impl FromStr for Almanac {
-  type Err = &'static str;
+  type Err = anyhow::Error;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
     let mut chunks = s.split("\n\n");
 
-    let (_, seed_list) = chunks
+    let seed_chunk = chunks
       .next()
-      .ok_or("Failed to find seeds")?
+      .ok_or(anyhow::anyhow!("Failed to find seeds"))?;
+    let (_, seed_list) = seed_chunk
       .split_once(": ")
-      .ok_or("Failed to parse seed list")?;
+      .ok_or(anyhow::anyhow!("Failed to parse seed list"))?;
 
     let seeds = seed_list
       .split_whitespace()
@@ -113,21 +694,69 @@ impl FromStr for Almanac {
       .collect();
 
     let mut conversions = vec![];
+    let mut categories = vec![];
+    let mut line_numbers = vec![];
+    // 1-based line number of the current chunk's first line
+    let mut line = seed_chunk.lines().count() + 2;
 
     while let Some(chunk) = chunks.next() {
-      let lines = chunk.lines().skip(1);
-      let ranges = lines.map(|line| line.parse()).collect::<Result<_, _>>()?;
+      line_numbers.push(
+        ((line + 1)..)
+          .take(chunk.lines().count().saturating_sub(1))
+          .collect(),
+      );
+      line += chunk.lines().count() + 1;
+
+      let mut lines = chunk.lines();
+      let (from, to) = lines
+        .next()
+        .and_then(|header| header.strip_suffix(" map:"))
+        .and_then(|header| header.split_once("-to-"))
+        .ok_or(anyhow::anyhow!("Failed to parse map header"))?;
+      let ranges = lines
+        .map(|line| line.parse())
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::msg)?;
 
       conversions.push(ranges);
+      categories.push((from.to_string(), to.to_string()));
     }
 
-    Ok(Self { seeds, conversions })
+    let mut almanac = Self {
+      seeds,
+      conversions,
+      categories,
+      line_numbers,
+    };
+
+    // the positional stage APIs chain the stages from seed to location, so
+    // put them in that order and refuse anything that can't be chained.
+    let order = almanac.path("seed", "location")?;
+    if let Some(unused) = (0..almanac.conversions.len()).find(|i| !order.contains(i)) {
+      return Err(CategoryError::Unused(almanac.stage_name(unused)).into());
+    }
+
+    almanac.conversions = order
+      .iter()
+      .map(|&i| almanac.conversions[i].clone())
+      .collect();
+    almanac.categories = order
+      .iter()
+      .map(|&i| almanac.categories[i].clone())
+      .collect();
+    almanac.line_numbers = order
+      .iter()
+      .map(|&i| almanac.line_numbers[i].clone())
+      .collect();
+
+    Ok(almanac)
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::Rng;
 
   const EXAMPLE_INPUT: &'static str = "seeds: 79 14 55 13
 
@@ -194,14 +823,7 @@ humidity-to-location map:
   #[test]
   fn part2_example() {
     let a: Almanac = EXAMPLE_INPUT.parse().expect("Failed to parse input");
-
-    let ans = a
-      .part2_seed_ranges()
-      .iter()
-      .flat_map(|range| range.clone().into_iter())
-      .map(|seed| a.seed_to_location(seed))
-      .min()
-      .unwrap();
+    let ans = a.seed_ranges_to_locations(a.part2_seed_ranges())[0].start;
 
     assert_eq!(ans, 46);
   }
@@ -209,15 +831,190 @@ humidity-to-location map:
   #[test]
   fn part2_solution() {
     let a: Almanac = INPUT.parse().expect("Failed to parse input");
+    let ans = a.seed_ranges_to_locations(a.part2_seed_ranges())[0].start;
 
-    let ans = a
-      .part2_seed_ranges()
+    assert_eq!(ans, 60294664);
+  }
+
+  fn random_almanac(rng: &mut Rng) -> Almanac {
+    let conversions: Vec<Vec<ConversionRange>> = (0..(1 + rng.below(4)))
+      .map(|_| {
+        (0..rng.below(5))
+          .map(|_| ConversionRange {
+            src_start: rng.below(60),
+            dest_start: rng.below(60),
+            len: rng.below(20),
+          })
+          .collect()
+      })
+      .collect();
+
+    let categories = (0..conversions.len())
+      .map(|i| (format!("c{}", i), format!("c{}", i + 1)))
+      .collect();
+
+    let line_numbers = conversions
       .iter()
-      .flat_map(|range| range.clone().into_iter())
-      .map(|seed| a.seed_to_location(seed))
-      .min()
-      .unwrap();
+      .map(|stage| (0..stage.len()).collect())
+      .collect();
 
-    assert_eq!(ans, 60294664);
+    Almanac {
+      seeds: vec![],
+      conversions,
+      categories,
+      line_numbers,
+    }
+  }
+
+  #[test]
+  fn ranges_match_pointwise() {
+    let mut rng = Rng(0x2023_0005);
+
+    for _ in 0..500 {
+      let a = random_almanac(&mut rng);
+      let seeds: Vec<Range<usize>> = (0..(1 + rng.below(3)))
+        .map(|_| {
+          let start = rng.below(80);
+          start..(start + rng.below(30))
+        })
+        .collect();
+
+      let ranged = a.seed_ranges_to_locations(seeds.clone());
+      let pointwise = normalize(
+        seeds
+          .iter()
+          .flat_map(|r| r.clone())
+          .map(|seed| a.seed_to_location(seed))
+          .map(|loc| loc..(loc + 1))
+          .collect(),
+      );
+
+      assert_eq!(ranged, pointwise, "{:?} from {:?}", a.conversions, seeds);
+    }
+  }
+
+  #[test]
+  fn composed_matches_stages() {
+    let a: Almanac = INPUT.parse().expect("Failed to parse input");
+    let m = a.compose(0..a.conversions.len());
+
+    for &seed in &a.seeds {
+      assert_eq!(m.lookup(seed), a.seed_to_location(seed));
+    }
+
+    let mut rng = Rng(0x2023_0034);
+
+    for _ in 0..500 {
+      let a = random_almanac(&mut rng);
+      let n = a.conversions.len();
+      let (from, to) = (rng.below(n + 1), rng.below(n + 1));
+      let stages = from.min(to)..from.max(to);
+      let m = a.compose(stages.clone());
+
+      for value in 0..100 {
+        let expected = stages
+          .clone()
+          .fold(value, |v, index| a.convert_stage(v, index));
+        assert_eq!(
+          m.lookup(value),
+          expected,
+          "{:?} {:?}",
+          a.conversions,
+          stages
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn preimages_match_pointwise() {
+    let a: Almanac = EXAMPLE_INPUT.parse().expect("Failed to parse input");
+    let seeds = a.locations_to_seeds(46..47);
+
+    assert!(seeds.iter().any(|r| r.contains(&82)));
+    assert_eq!(a.explain(82), vec![82, 84, 84, 84, 77, 45, 46, 46]);
+
+    let mut rng = Rng(0x2023_0035);
+
+    for _ in 0..500 {
+      let a = random_almanac(&mut rng);
+      let start = rng.below(80);
+      let locations = start..(start + rng.below(10));
+      let seeds = a.locations_to_seeds(locations.clone());
+
+      for seed in 0..120 {
+        assert_eq!(
+          seeds.iter().any(|r| r.contains(&seed)),
+          locations.contains(&a.seed_to_location(seed)),
+          "{:?} seed {}",
+          a.conversions,
+          seed
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn named_categories() {
+    let a: Almanac = EXAMPLE_INPUT.parse().expect("Failed to parse input");
+
+    assert_eq!(a.convert("seed", "location", 79), Ok(82));
+    assert_eq!(a.convert("soil", "humidity", 81), Ok(78));
+
+    // swap the first two blocks; the stages should be put back in order.
+    let mut blocks: Vec<&str> = EXAMPLE_INPUT.split("\n\n").collect();
+    blocks.swap(1, 2);
+    let shuffled: Almanac = blocks.join("\n\n").parse().expect("Failed to parse input");
+
+    assert_eq!(shuffled.categories, a.categories);
+    assert_eq!(shuffled.seed_to_location(79), 82);
+
+    // stages which can't be chained from seed to location are rejected
+    // rather than applied in file order.
+    let error = |s: &str| {
+      s.parse::<Almanac>()
+        .expect_err("Almanac should be rejected")
+        .downcast::<CategoryError>()
+        .expect("Expected a category error")
+    };
+
+    assert_eq!(
+      error("seeds: 1\n\nseed-to-soil map:\n5 1 1\n\nfertilizer-to-location map:\n9 5 1"),
+      CategoryError::NoPath("seed".into(), "location".into())
+    );
+    assert_eq!(
+      error("seeds: 1\n\nseed-to-soil map:\n\nsoil-to-location map:\n\nseed-to-location map:\n"),
+      CategoryError::Ambiguous("seed".into(), "location".into())
+    );
+    assert_eq!(
+      error("seeds: 1\n\nseed-to-location map:\n\nsoil-to-water map:\n"),
+      CategoryError::Unused("soil-to-water".into())
+    );
+  }
+
+  #[test]
+  fn validate_solution() {
+    let a: Almanac = INPUT.parse().expect("Failed to parse input");
+    assert_eq!(a.validate(), vec![]);
+
+    let a: Almanac = EXAMPLE_INPUT.parse().expect("Failed to parse input");
+    let coverage = a.coverage(a.part2_seed_ranges());
+
+    assert_eq!(coverage.len(), 7);
+    assert!(coverage[0].identity.is_empty());
+    assert_eq!(coverage[1].stage, "soil-to-fertilizer");
+    assert_eq!(coverage[1].identity, vec![57..70, 81..95]);
+
+    let a: Almanac = format!("seeds: 1\n\nseed-to-location map:\n0 {} 2", usize::MAX)
+      .parse()
+      .expect("Failed to parse input");
+    assert_eq!(
+      a.validate(),
+      vec![Issue {
+        stage: "seed-to-location".into(),
+        line: 4,
+        problem: Problem::Overflow
+      }]
+    );
   }
 }
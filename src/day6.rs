@@ -1,9 +1,81 @@
+use num::{BigUint, CheckedSub};
+
+use crate::parse_error;
+use crate::ParseError;
+
 const INPUT: &'static str = include_str!("../inputs/day6.txt");
 
+/// How the columns of the race listings should be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+  /// Each column is a separate race.
+  Spaced,
+  /// The spaces are kerning; all of the digits make up a single race.
+  Kerned,
+}
+
 #[derive(Debug, Clone, Copy, Default)]
-struct Race {
-  record: usize,
-  time: usize,
+pub struct Race<T = usize> {
+  record: T,
+  time: T,
+}
+
+impl<T> Race<T>
+where
+  T: Clone + Into<BigUint> + TryFrom<BigUint>,
+{
+  /// Create a race over any unsigned integer type, including `u128` and
+  /// `BigUint`.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day6::Race;
+  ///
+  /// let race = Race::of(200u128, 30u128);
+  ///
+  /// assert_eq!(race.win_bounds(), Some((11, 19)));
+  /// assert_eq!(race.win_counts(), 9);
+  /// ```
+  pub fn of(record: T, time: T) -> Self {
+    Self { record, time }
+  }
+
+  /// Return the shortest and longest button hold times (inclusive) which
+  /// beat the record, or `None` if the record can't be beaten.
+  ///
+  /// Holding for `h` travels `h * (time - h)`, so the winning holds lie
+  /// strictly between the roots of `h^2 - time * h + record = 0`. All of the
+  /// arithmetic is done on big integers so large races can't overflow.
+  pub fn win_bounds(&self) -> Option<(T, T)> {
+    let time: BigUint = self.time.clone().into();
+    let record: BigUint = self.record.clone().into();
+    let beats = |h: &BigUint| h * (&time - h) > record;
+
+    let discriminant = (&time * &time).checked_sub(&(&record * 4u32))?;
+    // isqrt rounds down, so this is at or just below the lower root; step
+    // up to the first hold which actually wins.
+    let mut lo = (&time - discriminant.sqrt()) / 2u32;
+    while lo <= &time / 2u32 && !beats(&lo) {
+      lo += 1u32;
+    }
+    if lo > &time / 2u32 || !beats(&lo) {
+      return None;
+    }
+
+    let hi = &time - &lo;
+    let convert = |n: BigUint| T::try_from(n).unwrap_or_else(|_| unreachable!());
+
+    Some((convert(lo), convert(hi)))
+  }
+
+  pub fn win_counts(&self) -> T {
+    let count = match self.win_bounds() {
+      Some((lo, hi)) => Into::<BigUint>::into(hi) + 1u32 - Into::<BigUint>::into(lo),
+      None => BigUint::default(),
+    };
+
+    T::try_from(count).unwrap_or_else(|_| unreachable!())
+  }
 }
 
 impl Race {
@@ -11,36 +83,45 @@ impl Race {
     Self { record, time }
   }
 
-  pub fn win_counts(&self) -> usize {
-    // find smallest+largest values for speed such that
-    // speed * (time - speed) > record;
-    let test = |n: &usize| n * (self.time - n) > self.record;
-    let mut range = 1..self.time;
+  /// Parse the race listings, reading the columns either as separate races
+  /// or, with `Layout::Kerned`, as the digits of a single race.
+  pub fn load_all(s: &str, layout: Layout) -> Result<Vec<Race>, ParseError> {
+    let mut lines = s.lines();
+    let times = Race::parse_line(lines.next(), "Time:", layout)?;
+    let records = Race::parse_line(lines.next(), "Distance:", layout)?;
 
-    if let Some(lo) = range.find(&test) {
-      let hi = range.rfind(&test).unwrap();
-      hi + 1 - lo
-    } else {
-      // no way to win
-      0
+    if times.len() != records.len() {
+      return Err(parse_error!(
+        "Found {} times but {} distances",
+        times.len(),
+        records.len()
+      ));
     }
+
+    Ok(
+      times
+        .into_iter()
+        .zip(records)
+        .map(|(time, record)| Race::new(record, time))
+        .collect(),
+    )
   }
 
-  pub fn load_all(s: &str) -> Vec<Race> {
-    let (top, bottom) = s.split_once("\n").expect("Failed to parse race listings");
-    let times = top.split_whitespace().skip(1);
-    let mut records = bottom.split_whitespace().skip(1);
+  fn parse_line(line: Option<&str>, label: &str, layout: Layout) -> Result<Vec<usize>, ParseError> {
+    let values = line
+      .and_then(|line| line.strip_prefix(label))
+      .ok_or_else(|| parse_error!("Missing {} line", label))?;
 
-    times
-      .map(|time| {
-        let time = time.parse::<usize>().expect("Failed to parse time");
-        let record = records
-          .next()
-          .expect("Missing record")
-          .parse::<usize>()
-          .expect("Failed to parse distance");
+    let values: Vec<String> = match layout {
+      Layout::Spaced => values.split_whitespace().map(str::to_string).collect(),
+      Layout::Kerned => vec![values.split_whitespace().collect()],
+    };
 
-        Race::new(record, time)
+    values
+      .iter()
+      .map(|v| {
+        v.parse()
+          .map_err(|_| parse_error!("Invalid {} value: {:?}", label, v))
       })
       .collect()
   }
@@ -52,6 +133,108 @@ impl Race {
       .reduce(|a, b| a * b)
       .unwrap_or(0)
   }
+
+  /// The same product as `p1_solution`, with the boats following `physics`.
+  pub fn p1_solution_with(races: &[Race], physics: &Physics) -> usize {
+    races
+      .iter()
+      .map(|r| r.strategy(physics).wins)
+      .reduce(|a, b| a * b)
+      .unwrap_or(0)
+  }
+
+  /// Return the best hold time for this race under the given physics,
+  /// along with how many hold times beat the record.
+  ///
+  /// Distance is concave in the hold time (it's the smaller of two concave
+  /// curves once the speed is capped), so the best hold and the edges of the
+  /// winning interval can all be found by binary search.
+  pub fn strategy(&self, physics: &Physics) -> Strategy {
+    let moving = self.time.saturating_sub(physics.start_delay);
+    let distance = |h: usize| physics.distance(self.time, h);
+    let record = self.record as u128;
+
+    // first hold where holding any longer stops helping
+    let hold = Race::first(0, moving, |h| distance(h + 1) <= distance(h));
+    let best = distance(hold);
+
+    let wins = if best > record {
+      let lo = Race::first(0, hold, |h| distance(h) > record);
+      // holding for the whole race never moves the boat, so `moving` itself
+      // never wins and the search can stop short of it.
+      let hi = Race::first(hold, moving, |h| distance(h) <= record);
+      hi - lo
+    } else {
+      0
+    };
+
+    Strategy {
+      hold,
+      distance: best,
+      margin: i128::try_from(best).unwrap_or(i128::MAX) - record as i128,
+      wins,
+    }
+  }
+
+  /// Return the first value in `lo..hi` for which `test` holds, given that
+  /// it holds for every value after that too. Returns `hi` if there is none.
+  fn first(mut lo: usize, mut hi: usize, test: impl Fn(usize) -> bool) -> usize {
+    while lo < hi {
+      let mid = lo + (hi - lo) / 2;
+      if test(mid) {
+        hi = mid;
+      } else {
+        lo = mid + 1;
+      }
+    }
+    lo
+  }
+}
+
+/// The rules for how a boat moves. The default is the puzzle's own rules:
+/// 1 mm/ms of speed per ms held, with no cap and no delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Physics {
+  /// Speed gained (mm/ms) for each ms the button is held.
+  pub acceleration: usize,
+  pub max_speed: Option<usize>,
+  /// Time (ms) at the start of the race before the button can be held.
+  pub start_delay: usize,
+}
+
+impl Default for Physics {
+  fn default() -> Self {
+    Self {
+      acceleration: 1,
+      max_speed: None,
+      start_delay: 0,
+    }
+  }
+}
+
+impl Physics {
+  /// How far a boat travels in a race of length `time` after holding the
+  /// button for `hold` ms.
+  pub fn distance(&self, time: usize, hold: usize) -> u128 {
+    let moving = time.saturating_sub(self.start_delay).saturating_sub(hold) as u128;
+    let speed = (self.acceleration as u128).saturating_mul(hold as u128);
+    let speed = self.max_speed.map_or(speed, |cap| speed.min(cap as u128));
+
+    speed.saturating_mul(moving)
+  }
+}
+
+/// The best way to play a single race.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strategy {
+  /// The shortest hold time which travels the furthest.
+  pub hold: usize,
+  pub distance: u128,
+  /// How far past the record the best hold travels; negative if the record
+  /// can't be beaten. Saturates at `i128::MAX`, as `distance` does.
+  pub margin: i128,
+  /// How many hold times beat the record.
+  pub wins: usize,
 }
 
 #[cfg(test)]
@@ -64,27 +247,110 @@ Distance:  9  40  200
 
   #[test]
   fn part1_example() {
-    let races = Race::load_all(EXAMPLE_INPUT);
+    let races = Race::load_all(EXAMPLE_INPUT, Layout::Spaced).expect("Failed to parse races");
     let score = Race::p1_solution(&races);
     assert_eq!(score, 288);
   }
 
   #[test]
   fn part1_solution() {
-    let races = Race::load_all(INPUT);
+    let races = Race::load_all(INPUT, Layout::Spaced).expect("Failed to parse races");
     let score = Race::p1_solution(&races);
     assert_eq!(score, 3316275);
   }
 
   #[test]
   fn part2_example() {
-    let r = Race::new(940200, 71530);
-    assert_eq!(r.win_counts(), 71503);
+    let races = Race::load_all(EXAMPLE_INPUT, Layout::Kerned).expect("Failed to parse races");
+    assert_eq!(races.len(), 1);
+    assert_eq!(races[0].win_counts(), 71503);
   }
 
   #[test]
   fn part2_solution() {
-    let r = Race::new(233_1011_1110_1487, 40828492);
-    assert_eq!(r.win_counts(), 27102791);
+    let races = Race::load_all(INPUT, Layout::Kerned).expect("Failed to parse races");
+    assert_eq!(races[0].win_counts(), 27102791);
+  }
+
+  #[test]
+  fn closed_form_bounds() {
+    assert_eq!(Race::new(9, 7).win_bounds(), Some((2, 5)));
+    assert_eq!(Race::new(200, 30).win_bounds(), Some((11, 19)));
+    // exactly matching the record isn't a win
+    assert_eq!(Race::new(9, 6).win_bounds(), None);
+    assert_eq!(Race::new(100, 6).win_counts(), 0);
+
+    let r = Race::of(u128::MAX / 8, u128::MAX / 2);
+    let (lo, hi) = r.win_bounds().expect("Race should be winnable");
+    assert_eq!(hi - lo + 1, r.win_counts());
+
+    let big = BigUint::from(10u32).pow(40);
+    let r = Race::of(&big * &big / 4u32 - 1u32, big.clone());
+    assert_eq!(r.win_bounds(), Some((&big / 2u32, &big / 2u32)));
+
+    for time in 0..60 {
+      for record in 0..(time * time / 4 + 2) {
+        let expected = (1..time).filter(|h| h * (time - h) > record).count();
+        assert_eq!(Race::new(record, time).win_counts(), expected);
+      }
+    }
+  }
+
+  #[test]
+  fn malformed_listings() {
+    assert!(Race::load_all("", Layout::Spaced).is_err());
+    assert!(Race::load_all("Time: 7 15\n", Layout::Spaced).is_err());
+    assert!(Race::load_all("Time: 7 15\nDistance: 9", Layout::Spaced).is_err());
+    assert!(Race::load_all("Time: 7 x\nDistance: 9 40", Layout::Kerned).is_err());
+    assert!(Race::load_all("Time: 7 15\nDistance: 9 40", Layout::Kerned).is_ok());
+  }
+
+  #[test]
+  fn physics_variations() {
+    let races = Race::load_all(EXAMPLE_INPUT, Layout::Spaced).expect("Failed to parse races");
+    let physics = Physics::default();
+
+    assert_eq!(Race::p1_solution_with(&races, &physics), 288);
+    assert_eq!(
+      races[0].strategy(&physics),
+      Strategy {
+        hold: 3,
+        distance: 12,
+        margin: 3,
+        wins: 4
+      }
+    );
+
+    let physics = Physics {
+      acceleration: 2,
+      max_speed: Some(10),
+      start_delay: 3,
+    };
+    for race in &races {
+      let moving = race.time - physics.start_delay;
+      let expected = (0..=moving)
+        .filter(|&h| physics.distance(race.time, h) > race.record as u128)
+        .count();
+      let best = (0..=moving)
+        .map(|h| physics.distance(race.time, h))
+        .max()
+        .unwrap();
+      let strategy = race.strategy(&physics);
+
+      assert_eq!(strategy.wins, expected);
+      assert_eq!(strategy.distance, best);
+    }
+
+    let hopeless = Race::new(1000, 10).strategy(&Physics::default());
+    assert_eq!((hopeless.wins, hopeless.margin), (0, 25 - 1000));
+
+    // distances past i128::MAX saturate rather than wrapping negative
+    let physics = Physics {
+      acceleration: usize::MAX,
+      ..Physics::default()
+    };
+    let strategy = Race::new(1, usize::MAX).strategy(&physics);
+    assert_eq!(strategy.distance, u128::MAX);
+    assert_eq!(strategy.margin, i128::MAX - 1);
   }
 }
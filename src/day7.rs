@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, str::FromStr};
+use std::{cmp::Ordering, error::Error, fmt::Display, str::FromStr};
 
 pub(crate) use crate::parse_error;
 use crate::ParseError;
@@ -7,7 +7,7 @@ const INPUT: &'static str = include_str!("../inputs/day7.txt");
 
 // PartialOrd/Ord derive here is just the order of the types, which is perfect
 // https://doc.rust-lang.org/core/cmp/trait.Ord.html#derivable
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum HandRank {
   HighCard,
   OnePair,
@@ -18,12 +18,12 @@ pub enum HandRank {
   FiveOfAKind,
 }
 
-// we implement PartialOrd below, so Ord uses that + Eq
-#[derive(Debug, PartialEq, Eq, Ord)]
+// we implement Ord below, ordering by the packed standard-rules key
+#[derive(Debug, PartialEq, Eq)]
 pub struct Hand {
-  cards: Vec<u32>,
+  cards: [u32; 5],
   bid: u32,
-  rank: HandRank,
+  key: u32,
 }
 
 impl Hand {
@@ -41,39 +41,232 @@ impl Hand {
       .sum()
   }
 
-  // for part2: replace Jack(11) with Joker(0) then re-calculate score
-  pub fn rescore_with_jokers(&mut self) {
-    for n in self.cards.iter_mut() {
-      if *n == 11 {
-        *n = 0;
-      }
+  /// Score the hands under the given rules, without changing them.
+  pub fn score_with(hands: &[Hand], rules: &Rules) -> Result<usize, RulesError> {
+    // the key fills the high bits, so sorting by (key, bid) is a plain
+    // integer sort which still orders by key first.
+    let mut ranked: Vec<u64> = hands
+      .iter()
+      .map(|h| Ok((rules.key(&h.cards)? as u64) << 32 | h.bid as u64))
+      .collect::<Result<_, _>>()?;
+    ranked.sort_unstable();
+
+    Ok(
+      ranked
+        .iter()
+        .enumerate()
+        .map(|(index, packed)| (index + 1) * (*packed as u32) as usize)
+        .sum(),
+    )
+  }
+
+  /// Create a hand, which must only hold cards known to the standard rules.
+  pub fn new(cards: [u32; 5], bid: u32) -> Result<Self, RulesError> {
+    let key = Rules::standard().key(&cards)?;
+    Ok(Self { cards, bid, key })
+  }
+
+  pub fn rank(&self) -> HandRank {
+    Rules::standard()
+      .rank(&self.cards)
+      .expect("Cards are checked when the hand is created")
+  }
+
+  /// Explain how this hand is ranked under the given rules.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day7::{Hand, Rules};
+  ///
+  /// let hand: Hand = "KJJK3 1".parse().unwrap();
+  ///
+  /// assert_eq!(hand.explain(&Rules::jokers()).unwrap().to_string(), "JJ → K, Four of a kind");
+  /// assert_eq!(hand.explain(&Rules::standard()).unwrap().to_string(), "Two pair");
+  /// ```
+  pub fn explain(&self, rules: &Rules) -> Result<Explanation, RulesError> {
+    rules.explain(&self.cards)
+  }
+}
+
+/// How ties between hands of the same rank are broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+  /// Compare the cards one at a time, in the order they were dealt.
+  Dealt,
+  /// Compare the cards from strongest to weakest, like poker.
+  Strongest,
+}
+
+/// A set of rules for ranking hands of Camel Cards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+  /// Every card value, from weakest to strongest.
+  order: Vec<u32>,
+  /// Cards which count as whichever card makes the best hand.
+  wild: Vec<u32>,
+  tie_break: TieBreak,
+}
+
+/// The most cards a rule set can order, since `Rules::key` packs each card's
+/// strength into four bits.
+pub const MAX_CARDS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesError {
+  /// The card order has more than `MAX_CARDS` cards.
+  TooManyCards(usize),
+  /// A card which isn't in the card order.
+  UnknownCard(u32),
+}
+
+impl Display for RulesError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RulesError::TooManyCards(n) => write!(
+        f,
+        "Rules order {} cards, but at most {} can be ranked",
+        n, MAX_CARDS
+      ),
+      RulesError::UnknownCard(c) => write!(f, "Card {} isn't in the rules' card order", c),
+    }
+  }
+}
+
+impl Error for RulesError {}
+
+impl Rules {
+  /// Create a rule set, with `order` listing every card from weakest to
+  /// strongest.
+  pub fn new(order: Vec<u32>, wild: Vec<u32>, tie_break: TieBreak) -> Result<Self, RulesError> {
+    if order.len() > MAX_CARDS {
+      return Err(RulesError::TooManyCards(order.len()));
+    }
+
+    Ok(Self {
+      order,
+      wild,
+      tie_break,
+    })
+  }
+
+  /// These rules, breaking ties the given way instead.
+  pub fn with_tie_break(self, tie_break: TieBreak) -> Self {
+    Self { tie_break, ..self }
+  }
+
+  /// The rules for part 1: no wild cards, aces high.
+  pub fn standard() -> Self {
+    Self {
+      order: (2..=14).collect(),
+      wild: vec![],
+      tie_break: TieBreak::Dealt,
+    }
+  }
+
+  /// The rules for part 2: jacks are wild, and are the weakest card.
+  pub fn jokers() -> Self {
+    Self {
+      order: [11].into_iter().chain(2..=10).chain(12..=14).collect(),
+      wild: vec![11],
+      tie_break: TieBreak::Dealt,
     }
-    self.rank = Hand::rank(&self.cards)
   }
 
-  pub fn new(cards: Vec<u32>, bid: u32) -> Self {
-    let rank = Hand::rank(&cards);
-    Self { cards, bid, rank }
+  /// The standard card order, with the given card wild.
+  pub fn wild(card: u32) -> Self {
+    Self {
+      wild: vec![card],
+      ..Self::standard()
+    }
+  }
+
+  /// The strength of a card under these rules, for breaking ties.
+  pub fn value(&self, card: u32) -> Result<usize, RulesError> {
+    self
+      .order
+      .iter()
+      .position(|&c| c == card)
+      .ok_or(RulesError::UnknownCard(card))
+  }
+
+  /// The key hands are sorted by under these rules, packed into a single
+  /// integer: the rank in the top bits, followed by four bits for each card
+  /// value in tie-break order.
+  pub fn key(&self, cards: &[u32; 5]) -> Result<u32, RulesError> {
+    let mut values = [0; 5];
+    for (value, &card) in values.iter_mut().zip(cards) {
+      *value = self.value(card)? as u32;
+    }
+
+    if self.tie_break == TieBreak::Strongest {
+      values.sort_by(|a, b| b.cmp(a));
+    }
+
+    Ok(
+      values
+        .iter()
+        .fold(self.rank(cards)? as u32, |key, &v| key << 4 | v),
+    )
+  }
+
+  /// Work out which card the wild cards should stand in for, and the groups
+  /// of matching cards that leaves.
+  pub fn explain(&self, cards: &[u32; 5]) -> Result<Explanation, RulesError> {
+    let rank = self.rank(cards)?;
+    let count = |card: u32| cards.iter().filter(|&&c| c == card).count();
+    let wilds: Vec<u32> = cards
+      .iter()
+      .copied()
+      .filter(|c| self.wild.contains(c))
+      .collect();
+
+    // wild cards always do best joining the largest group, and the
+    // strongest one on a tie. If every card is wild, they become the
+    // strongest card. `rank` has already checked every other card is known.
+    let target = cards
+      .iter()
+      .copied()
+      .filter(|c| !self.wild.contains(c))
+      .max_by_key(|&c| (count(c), self.value(c).ok()))
+      .or(self.order.last().copied())
+      .unwrap_or(cards[0]);
+
+    let effective = cards.map(|c| if self.wild.contains(&c) { target } else { c });
+    let mut groups: Vec<(u32, usize)> = vec![];
+    for &card in effective.iter() {
+      if !groups.iter().any(|&(c, _)| c == card) {
+        groups.push((card, effective.iter().filter(|&&c| c == card).count()));
+      }
+    }
+    groups.sort_by_key(|&(card, n)| std::cmp::Reverse((n, self.value(card).ok())));
+
+    Ok(Explanation {
+      rank,
+      groups,
+      substitutions: wilds.into_iter().map(|w| (w, target)).collect(),
+    })
   }
 
-  fn rank(cards: &[u32]) -> HandRank {
-    let mut buckets = [0u32; 15];
+  /// Rank a hand, or return an error if it holds a card these rules don't
+  /// know.
+  pub fn rank(&self, cards: &[u32; 5]) -> Result<HandRank, RulesError> {
+    let mut buckets = [0u32; MAX_CARDS];
     let mut jokers = 0;
 
     // count how many instances of each card we have.
-    cards.iter().for_each(|n| {
-      if *n == 0 {
-        // count jokers seperately.
+    for &card in cards {
+      if self.wild.contains(&card) {
+        // count wild cards seperately.
         jokers += 1;
       } else {
-        buckets[*n as usize] += 1;
+        buckets[self.value(card)?] += 1;
       }
-    });
+    }
 
     // the max number of the same card we have determines our hand.
     let max = buckets.iter().max().unwrap();
 
-    match max + jokers {
+    Ok(match max + jokers {
       5 => HandRank::FiveOfAKind,
       4 => HandRank::FourOfAKind,
       3 => {
@@ -92,22 +285,21 @@ impl Hand {
           _ => HandRank::OnePair,
         }
       }
-      1 => HandRank::HighCard,
-      _ => unreachable!("More than 5 of a kind of no cards.  what?"),
-    }
+      // five cards always make at least a high card.
+      _ => HandRank::HighCard,
+    })
   }
 }
 
 impl PartialOrd for Hand {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-    self
-      .rank
-      .partial_cmp(&other.rank) // order by rank
-      .map(|ord| match ord {
-        // if rank is the same, order by cards
-        Ordering::Equal => self.cards.partial_cmp(&other.cards).unwrap(),
-        _ => ord,
-      })
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Hand {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.key.cmp(&other.key)
   }
 }
 
@@ -129,19 +321,73 @@ impl FromStr for Hand {
         'Q' => Ok(12),
         'J' => Ok(11),
         'T' => Ok(10),
-        n => ch
-          .to_digit(10)
-          .ok_or_else(|| parse_error!("Invalid character: {}", n)),
+        '2'..='9' => Ok(ch.to_digit(10).unwrap()),
+        n => Err(parse_error!("Invalid character: {}", n)),
       })
       .collect::<Result<_, _>>()?;
+    let cards: [u32; 5] = cards
+      .try_into()
+      .map_err(|c: Vec<u32>| parse_error!("Expected 5 cards, found {}", c.len()))?;
 
-    Ok(Hand::new(cards, bid))
+    Ok(Hand::new(cards, bid)?)
+  }
+}
+
+/// How a hand was ranked under a set of rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+  pub rank: HandRank,
+  /// Each card in the hand (after substituting wild cards) and how many
+  /// times it appears, largest group first.
+  pub groups: Vec<(u32, usize)>,
+  /// Each wild card in the hand, and the card it stood in for.
+  pub substitutions: Vec<(u32, u32)>,
+}
+
+impl Display for Explanation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(&(_, target)) = self.substitutions.first() {
+      let wilds: String = self
+        .substitutions
+        .iter()
+        .map(|&(w, _)| card_char(w))
+        .collect();
+      write!(f, "{} → {}, ", wilds, card_char(target))?;
+    }
+    write!(f, "{}", self.rank)
+  }
+}
+
+impl Display for HandRank {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      HandRank::HighCard => "High card",
+      HandRank::OnePair => "One pair",
+      HandRank::TwoPair => "Two pair",
+      HandRank::ThreeOfAKind => "Three of a kind",
+      HandRank::FullHouse => "Full house",
+      HandRank::FourOfAKind => "Four of a kind",
+      HandRank::FiveOfAKind => "Five of a kind",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+fn card_char(card: u32) -> char {
+  match card {
+    14 => 'A',
+    13 => 'K',
+    12 => 'Q',
+    11 => 'J',
+    10 => 'T',
+    n => char::from_digit(n, 10).unwrap_or('?'),
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::Rng;
 
   const EXAMPLE_INPUT: &'static str = "32T3K 765
 T55J5 684
@@ -164,17 +410,150 @@ QQQJA 483
 
   #[test]
   fn part2_example() {
-    let mut hands = Hand::load_all(EXAMPLE_INPUT).expect("Failed to load hands");
-    hands.iter_mut().for_each(|h| h.rescore_with_jokers());
+    let hands = Hand::load_all(EXAMPLE_INPUT).expect("Failed to load hands");
 
-    assert_eq!(Hand::score(&mut hands), 5_905);
+    assert_eq!(Hand::score_with(&hands, &Rules::jokers()), Ok(5_905));
+    assert_eq!(Hand::score_with(&hands, &Rules::standard()), Ok(6_440));
   }
 
   #[test]
   fn part2_solution() {
-    let mut hands = Hand::load_all(INPUT).expect("Failed to load hands");
-    hands.iter_mut().for_each(|h| h.rescore_with_jokers());
+    let hands = Hand::load_all(INPUT).expect("Failed to load hands");
+
+    assert_eq!(Hand::score_with(&hands, &Rules::jokers()), Ok(251_515_496));
+  }
+
+  #[test]
+  fn custom_rules() {
+    let twos = Rules::wild(2);
+
+    assert_eq!(twos.rank(&[2, 2, 3, 4, 5]), Ok(HandRank::ThreeOfAKind));
+    assert_eq!(twos.rank(&[2, 2, 2, 2, 2]), Ok(HandRank::FiveOfAKind));
+    assert_eq!(
+      Rules::standard().rank(&[2, 2, 3, 4, 5]),
+      Ok(HandRank::OnePair)
+    );
+    // a wild two still only breaks ties as a two
+    assert!(twos.key(&[2, 3, 3, 4, 5]).unwrap() < twos.key(&[3, 3, 3, 4, 5]).unwrap());
+
+    let poker = Rules::standard().with_tie_break(TieBreak::Strongest);
+    assert!(poker.key(&[2, 14, 3, 4, 5]).unwrap() > poker.key(&[13, 12, 3, 4, 5]).unwrap());
+    assert!(
+      Rules::standard().key(&[2, 14, 3, 4, 5]).unwrap()
+        < Rules::standard().key(&[13, 12, 3, 4, 5]).unwrap()
+    );
+
+    // keys only have room for sixteen distinct card strengths
+    let wide = Rules::new((0..16).rev().collect(), vec![], TieBreak::Dealt).expect("Valid rules");
+    assert!(wide.key(&[0, 2, 3, 4, 5]).unwrap() > wide.key(&[1, 2, 3, 4, 5]).unwrap());
+    assert_eq!(
+      Rules::new((1..=17).collect(), vec![], TieBreak::Dealt),
+      Err(RulesError::TooManyCards(17))
+    );
+  }
+
+  #[test]
+  fn invalid_hands() {
+    assert!("32T3K3 765".parse::<Hand>().is_err());
+    assert!("32T3 765".parse::<Hand>().is_err());
+    assert!("3213K 765".parse::<Hand>().is_err());
+    assert!("3203K 765".parse::<Hand>().is_err());
+    assert!("32X3K 765".parse::<Hand>().is_err());
+
+    // cards the rules don't know are an error, not a panic
+    assert!(Hand::new([2, 3, 4, 5, 15], 1).is_err());
+    assert_eq!(
+      Rules::standard().rank(&[2, 3, 4, 5, 99]),
+      Err(RulesError::UnknownCard(99))
+    );
+    assert_eq!(
+      Rules::jokers().key(&[1, 3, 4, 5, 6]),
+      Err(RulesError::UnknownCard(1))
+    );
+    let hands = Hand::load_all(EXAMPLE_INPUT).expect("Failed to load hands");
+    let short = Rules::new((2..=13).collect(), vec![], TieBreak::Dealt).unwrap();
+    assert_eq!(
+      Hand::score_with(&hands, &short),
+      Err(RulesError::UnknownCard(14))
+    );
+  }
+
+  #[test]
+  fn explanations() {
+    let hands = Hand::load_all(INPUT).expect("Failed to load hands");
+    let rules = Rules::jokers();
+
+    // the substituted hand always ranks the same as the wild one
+    for hand in &hands {
+      let explanation = hand.explain(&rules).unwrap();
+      let effective: Vec<u32> = explanation
+        .groups
+        .iter()
+        .flat_map(|&(card, n)| std::iter::repeat_n(card, n))
+        .collect();
+
+      let effective: [u32; 5] = effective.try_into().unwrap();
+      assert_eq!(Rules::standard().rank(&effective), Ok(explanation.rank));
+    }
+
+    let hand: Hand = "JJJJJ 1".parse().unwrap();
+    let explanation = hand.explain(&rules).unwrap();
+    assert_eq!(explanation.to_string(), "JJJJJ → A, Five of a kind");
+    assert_eq!(explanation.groups, vec![(14, 5)]);
+  }
+
+  #[test]
+  fn packed_keys_match_rank_order() {
+    let mut rng = Rng(0x2023_0007);
+    let rules = Rules::jokers();
+
+    for _ in 0..10_000 {
+      let a: [u32; 5] = std::array::from_fn(|_| 2 + rng.below(13) as u32);
+      let b: [u32; 5] = std::array::from_fn(|_| 2 + rng.below(13) as u32);
+      let unpacked = |cards: &[u32; 5]| {
+        let values: Vec<usize> = cards.iter().map(|&c| rules.value(c).unwrap()).collect();
+        (rules.rank(cards).unwrap(), values)
+      };
+
+      assert_eq!(
+        rules.key(&a).unwrap().cmp(&rules.key(&b).unwrap()),
+        unpacked(&a).cmp(&unpacked(&b))
+      );
+    }
+  }
+
+  /// Compares sorting by packed keys against sorting by (rank, card values).
+  /// Run with `cargo test --release day7::tests::bench_sort -- --ignored --nocapture`.
+  #[test]
+  #[ignore]
+  fn bench_sort() {
+    let mut rng = Rng(0x2023_0042);
+    let rules = Rules::standard();
+    let hands: Vec<[u32; 5]> = (0..1_000_000)
+      .map(|_| std::array::from_fn(|_| 2 + rng.below(13) as u32))
+      .collect();
+
+    let mut unpacked: Vec<(HandRank, Vec<usize>)> = hands
+      .iter()
+      .map(|cards| {
+        (
+          rules.rank(cards).unwrap(),
+          cards.iter().map(|&c| rules.value(c).unwrap()).collect(),
+        )
+      })
+      .collect();
+    let start = std::time::Instant::now();
+    unpacked.sort();
+    let unpacked_time = start.elapsed();
+
+    let mut packed: Vec<u32> = hands
+      .iter()
+      .map(|cards| rules.key(cards).unwrap())
+      .collect();
+    let start = std::time::Instant::now();
+    packed.sort_unstable();
+    let packed_time = start.elapsed();
 
-    assert_eq!(Hand::score(&mut hands), 251_515_496);
+    println!("unpacked: {:?}, packed: {:?}", unpacked_time, packed_time);
   }
 }
@@ -1,4 +1,4 @@
-use num::integer::lcm;
+use num::Integer;
 use regex::Regex;
 use std::{collections::HashMap, str::FromStr};
 
@@ -26,9 +26,13 @@ impl TryFrom<char> for Dir {
 }
 
 #[derive(Debug)]
-struct Map {
+pub struct Map {
   pattern: Vec<Dir>,
-  nodes: HashMap<String, (String, String)>,
+  /// Node names, indexed by id.
+  names: Vec<String>,
+  ids: HashMap<String, usize>,
+  /// The (left, right) neighbours of each node, indexed by id.
+  links: Vec<[usize; 2]>,
 }
 
 impl FromStr for Map {
@@ -52,23 +56,58 @@ impl FromStr for Map {
         let c = r
           .captures(line)
           .ok_or(parse_error!("Failed to parse line: {}", line))?;
-        Ok::<_, anyhow::Error>((c[1].to_string(), (c[2].to_string(), c[3].to_string())))
+        Ok::<_, anyhow::Error>((c[1].to_string(), c[2].to_string(), c[3].to_string()))
       })
-      .collect::<Result<_, _>>()?;
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let names: Vec<String> = nodes.iter().map(|(name, _, _)| name.clone()).collect();
+    let ids: HashMap<String, usize> = names
+      .iter()
+      .enumerate()
+      .map(|(id, name)| (name.clone(), id))
+      .collect();
+    let id = |name: &str| {
+      ids
+        .get(name)
+        .copied()
+        .ok_or(parse_error!("Unknown node: {}", name))
+    };
+
+    let links = nodes
+      .iter()
+      .map(|(_, l, r)| Ok([id(l)?, id(r)?]))
+      .collect::<Result<_, ParseError>>()?;
 
-    Ok(Self { pattern, nodes })
+    Ok(Self {
+      pattern,
+      names,
+      ids,
+      links,
+    })
   }
 }
 
 impl Map {
-  pub fn step(&self, from: &str, n: usize) -> &str {
-    let (l, r) = &self.nodes[from];
+  pub fn id(&self, name: &str) -> Option<usize> {
+    self.ids.get(name).copied()
+  }
+
+  pub fn name(&self, id: usize) -> &str {
+    &self.names[id]
+  }
+
+  /// Return the node reached by taking step `n` of the pattern from `from`.
+  pub fn step_id(&self, from: usize, n: usize) -> usize {
     match self.pattern[n % self.pattern.len()] {
-      Dir::Left => l,
-      Dir::Right => r,
+      Dir::Left => self.links[from][0],
+      Dir::Right => self.links[from][1],
     }
   }
 
+  pub fn step(&self, from: &str, n: usize) -> &str {
+    self.name(self.step_id(self.ids[from], n))
+  }
+
   pub fn run_p1(&self) -> usize {
     self.steps_to_complete("AAA", |node| node == "ZZZ")
   }
@@ -77,11 +116,11 @@ impl Map {
   where
     F: Fn(&str) -> bool,
   {
-    let mut current = start;
+    let mut current = self.ids[start];
     let mut steps = 0;
 
-    while !is_end(current) {
-      current = self.step(current, steps);
+    while !is_end(self.name(current)) {
+      current = self.step_id(current, steps);
       steps += 1;
     }
 
@@ -89,16 +128,443 @@ impl Map {
   }
 
   pub fn run_p2(&self) -> usize {
+    let starts: Vec<&str> = self
+      .names
+      .iter()
+      .filter(|name| name.ends_with("A"))
+      .map(|name| name.as_str())
+      .collect();
+
+    self
+      .solve(&starts, |n| n.ends_with("Z"))
+      .expect("No solution")
+  }
+
+  /// Render the network in Graphviz DOT format. Edges are labelled with the
+  /// direction(s) taking them; start (`..A`) and end (`..Z`) nodes are
+  /// highlighted. Names are quoted, since they may start with a digit.
+  ///
+  /// Render with e.g. `dot -Tsvg map.dot > map.svg`.
+  pub fn to_dot(&self) -> String {
+    let mut out = String::from("digraph {\n");
+
+    for (id, name) in self.names.iter().enumerate() {
+      if name.ends_with('A') {
+        out.push_str(&format!(
+          "  \"{}\" [style=filled, fillcolor=green];\n",
+          name
+        ));
+      } else if name.ends_with('Z') {
+        out.push_str(&format!("  \"{}\" [style=filled, fillcolor=red];\n", name));
+      }
+
+      let [l, r] = self.links[id];
+      if l == r {
+        out.push_str(&format!(
+          "  \"{}\" -> \"{}\" [label=\"LR\"];\n",
+          name, self.names[l]
+        ));
+      } else {
+        out.push_str(&format!(
+          "  \"{}\" -> \"{}\" [label=\"L\"];\n",
+          name, self.names[l]
+        ));
+        out.push_str(&format!(
+          "  \"{}\" -> \"{}\" [label=\"R\"];\n",
+          name, self.names[r]
+        ));
+      }
+    }
+
+    out.push_str("}\n");
+    out
+  }
+
+  /// Return every node which can never be visited when following the
+  /// pattern from `start`, or `None` if there is no such node.
+  pub fn unreachable_from(&self, start: &str) -> Option<Vec<&str>> {
+    let len = self.pattern.len();
+    let mut seen = vec![false; self.names.len() * len];
+    let mut visited = vec![false; self.names.len()];
+    let mut queue = vec![(self.id(start)?, 0)];
+
+    while let Some((id, n)) = queue.pop() {
+      if std::mem::replace(&mut seen[id * len + n], true) {
+        continue;
+      }
+      visited[id] = true;
+      queue.push((self.step_id(id, n), (n + 1) % len));
+    }
+
+    Some(
+      self
+        .names
+        .iter()
+        .zip(visited)
+        .filter(|(_, visited)| !visited)
+        .map(|(name, _)| name.as_str())
+        .collect(),
+    )
+  }
+
+  /// Return every start node which never reaches an end node, and so would
+  /// leave `steps_to_complete` looping forever.
+  pub fn stuck_starts<S, E>(&self, is_start: S, is_end: E) -> Vec<&str>
+  where
+    S: Fn(&str) -> bool,
+    E: Fn(&str) -> bool,
+  {
     self
-      .nodes
-      .keys()
-      .filter(|key| key.ends_with("A"))
-      .map(|node| self.steps_to_complete(node, |n| n.ends_with("Z")))
-      .reduce(|a, b| lcm(a, b))
-      .unwrap()
+      .names
+      .iter()
+      .map(|name| name.as_str())
+      .filter(|name| is_start(name))
+      .filter(|name| {
+        let cycle = self.cycle(name, &is_end);
+        cycle.pre_hits.is_empty() && cycle.hits.is_empty()
+      })
+      .collect()
+  }
+
+  /// Return the strongly connected components of the network, using either
+  /// edge out of each node. Components are listed in reverse topological
+  /// order, so nothing leaves a component except to one listed before it.
+  pub fn components(&self) -> Vec<Vec<&str>> {
+    // iterative Tarjan's algorithm
+    let n = self.names.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut counter = 0;
+
+    for root in 0..n {
+      if index[root] != usize::MAX {
+        continue;
+      }
+
+      // (node, which of its two edges to look at next)
+      let mut work = vec![(root, 0)];
+
+      while let Some(&mut (v, ref mut edge)) = work.last_mut() {
+        if *edge == 0 && index[v] == usize::MAX {
+          index[v] = counter;
+          low[v] = counter;
+          counter += 1;
+          stack.push(v);
+          on_stack[v] = true;
+        }
+
+        if *edge < 2 {
+          let w = self.links[v][*edge];
+          *edge += 1;
+
+          if index[w] == usize::MAX {
+            work.push((w, 0));
+          } else if on_stack[w] {
+            low[v] = low[v].min(index[w]);
+          }
+          continue;
+        }
+
+        work.pop();
+        if let Some(&(parent, _)) = work.last() {
+          low[parent] = low[parent].min(low[v]);
+        }
+
+        if low[v] == index[v] {
+          let mut component = vec![];
+          loop {
+            let w = stack.pop().unwrap();
+            on_stack[w] = false;
+            component.push(self.names[w].as_str());
+            if w == v {
+              break;
+            }
+          }
+          components.push(component);
+        }
+      }
+    }
+
+    components
+  }
+
+  /// Build a jump table for finding where nodes end up after any number
+  /// of steps. Building it takes a full pass of the pattern from every
+  /// node, so keep it around for repeated queries.
+  pub fn jumps(&self) -> Jumps<'_> {
+    Jumps::new(self)
+  }
+
+  /// Follow a ghost from `start` until it repeats a (node, pattern index)
+  /// state, recording every step at which it is on an end node.
+  pub fn cycle<F>(&self, start: &str, is_end: F) -> Cycle
+  where
+    F: Fn(&str) -> bool,
+  {
+    let mut seen: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut ends = vec![];
+    let mut current = self.ids[start];
+    let mut steps = 0;
+
+    let offset = loop {
+      let state = (current, steps % self.pattern.len());
+      if let Some(&first) = seen.get(&state) {
+        break first;
+      }
+      seen.insert(state, steps);
+
+      if is_end(self.name(current)) {
+        ends.push(steps);
+      }
+      current = self.step_id(current, steps);
+      steps += 1;
+    };
+
+    let (pre_hits, hits) = ends.into_iter().partition(|&t| t < offset);
+
+    Cycle {
+      offset,
+      len: steps - offset,
+      pre_hits,
+      hits,
+    }
+  }
+
+  /// Find the first step at which every ghost is on an end node at once.
+  ///
+  /// Each ghost eventually loops, so past the longest lead-in the answer
+  /// must line up with one end node in each ghost's loop; every combination
+  /// of those is solved with the Chinese Remainder Theorem, or if there are
+  /// more than `MAX_COMBINATIONS` the loops are scanned instead. Any answer
+  /// during a lead-in is found by checking those steps directly.
+  ///
+  /// Scanning checks at most `MAX_SCAN` steps; if the loops' combined period
+  /// needs more than that, this returns an error rather than running for an
+  /// impractically long time.
+  pub fn solve<F>(&self, starts: &[&str], is_end: F) -> anyhow::Result<usize>
+  where
+    F: Fn(&str) -> bool,
+  {
+    let cycles: Vec<Cycle> = starts.iter().map(|s| self.cycle(s, &is_end)).collect();
+
+    for (start, cycle) in starts.iter().zip(cycles.iter()) {
+      if cycle.pre_hits.is_empty() && cycle.hits.is_empty() {
+        anyhow::bail!("Ghost starting at {} never reaches an end node", start);
+      }
+    }
+
+    let lead_in = cycles.iter().map(|c| c.offset).max().unwrap_or(0);
+
+    if let Some(t) = (0..lead_in).find(|&t| cycles.iter().all(|c| c.is_hit(t))) {
+      return Ok(t);
+    }
+
+    // past the lead-ins each ghost is on an end node for `hits.len()` of
+    // every `len` steps; trying every combination of those is only
+    // worthwhile while there aren't too many of them.
+    let combinations = cycles
+      .iter()
+      .try_fold(1usize, |n, c| n.checked_mul(c.hits.len()));
+    let found = match combinations {
+      Some(n) if n <= MAX_COMBINATIONS => combine(&cycles, lead_in)?,
+      _ => scan(&cycles, lead_in)?,
+    };
+
+    found.ok_or_else(|| anyhow::anyhow!("The ghosts are never all on end nodes at once"))
   }
 }
 
+/// The most combinations of looping end nodes `Map::solve` will solve with
+/// the CRT before falling back to `scan`.
+const MAX_COMBINATIONS: usize = 1 << 16;
+
+/// The most candidate steps `scan` will check before giving up.
+const MAX_SCAN: usize = 1 << 28;
+
+/// Solve every combination of one looping end node per ghost with the CRT,
+/// returning the first step at or after `lead_in` where they all line up.
+fn combine(cycles: &[Cycle], lead_in: usize) -> anyhow::Result<Option<usize>> {
+  let mut best: Option<usize> = None;
+  let mut choice = vec![0; cycles.len()];
+
+  'combinations: loop {
+    let mut system = Some((0, 1));
+    for (c, &i) in cycles.iter().zip(choice.iter()) {
+      let Some(&hit) = c.hits.get(i) else {
+        system = None;
+        break;
+      };
+      system = match system {
+        Some(acc) => crt(acc, (hit as i128 % c.len as i128, c.len as i128))?,
+        None => None,
+      };
+    }
+
+    if let Some((r, m)) = system {
+      // smallest t = r (mod m) which is past every lead-in
+      let behind = (lead_in as i128 - r).max(0);
+      let t = Integer::div_ceil(&behind, &m)
+        .checked_mul(m)
+        .and_then(|k| k.checked_add(r))
+        .ok_or_else(|| anyhow::anyhow!("Overflow finding a step congruent to {} mod {}", r, m))?;
+      let t = usize::try_from(t)?;
+      best = Some(best.map_or(t, |b| b.min(t)));
+    }
+
+    for (i, c) in cycles.iter().enumerate() {
+      choice[i] += 1;
+      if choice[i] < c.hits.len() {
+        continue 'combinations;
+      }
+      choice[i] = 0;
+    }
+    break;
+  }
+
+  Ok(best)
+}
+
+/// Check each looping end node of the ghost with the fewest of them, over
+/// one full period of every ghost's loop starting from `lead_in`. The cost
+/// is that ghost's hits times the number of its loops in the period, so
+/// this errors instead if that is more than `MAX_SCAN` steps, or if the
+/// period doesn't fit in a `usize`.
+fn scan(cycles: &[Cycle], lead_in: usize) -> anyhow::Result<Option<usize>> {
+  let period = cycles
+    .iter()
+    .try_fold(1usize, |p, c| (p / p.gcd(&c.len)).checked_mul(c.len))
+    .ok_or_else(|| anyhow::anyhow!("The ghosts' combined period overflows"))?;
+  let end = lead_in
+    .checked_add(period)
+    .ok_or_else(|| anyhow::anyhow!("The ghosts' combined period overflows"))?;
+
+  let Some(sparsest) = cycles
+    .iter()
+    .min_by_key(|c| c.hits.len().saturating_mul(period / c.len))
+  else {
+    return Ok(None);
+  };
+
+  let steps = sparsest.hits.len().saturating_mul(period / sparsest.len);
+  if steps > MAX_SCAN {
+    anyhow::bail!(
+      "Finding when the ghosts line up needs {} steps checked, more than the limit of {}",
+      steps,
+      MAX_SCAN
+    );
+  }
+
+  Ok(
+    (0..)
+      .map(|k| k * sparsest.len)
+      .take_while(|&base| base < end)
+      .flat_map(|base| sparsest.hits.iter().map(move |&hit| hit + base))
+      .filter(|&t| (lead_in..end).contains(&t))
+      .find(|&t| cycles.iter().all(|c| c.is_hit(t))),
+  )
+}
+
+/// Precomputed jumps for finding where a node ends up after many steps.
+/// `levels[k][id]` is where `id` ends up after `2^k` full passes of the
+/// pattern, so any number of passes takes one lookup per bit.
+#[derive(Debug)]
+pub struct Jumps<'a> {
+  map: &'a Map,
+  levels: Vec<Vec<usize>>,
+}
+
+impl<'a> Jumps<'a> {
+  /// Build enough levels to answer queries of any number of steps.
+  pub fn new(map: &'a Map) -> Self {
+    let pass = (0..map.names.len())
+      .map(|id| (0..map.pattern.len()).fold(id, |at, n| map.step_id(at, n)))
+      .collect();
+    let mut levels: Vec<Vec<usize>> = vec![pass];
+    // one level per bit of the largest number of passes
+    let max_passes = usize::MAX / map.pattern.len();
+    let bits = (usize::BITS - max_passes.leading_zeros()) as usize;
+
+    while levels.len() < bits {
+      let last = levels.last().unwrap();
+      levels.push(last.iter().map(|&next| last[next]).collect());
+    }
+
+    Self { map, levels }
+  }
+
+  /// Return the node `start` ends up at after `steps` steps.
+  pub fn after(&self, start: usize, steps: usize) -> usize {
+    let len = self.map.pattern.len();
+    let (passes, rest) = (steps / len, steps % len);
+
+    let at = self
+      .levels
+      .iter()
+      .enumerate()
+      .filter(|(k, _)| passes >> k & 1 == 1)
+      .fold(start, |at, (_, level)| level[at]);
+
+    (0..rest).fold(at, |at, n| self.map.step_id(at, n))
+  }
+
+  /// As `after`, by node name.
+  pub fn position_after(&self, start: &str, steps: usize) -> &'a str {
+    self.map.name(self.after(self.map.ids[start], steps))
+  }
+}
+
+/// The path a single ghost takes. After `offset` steps it repeats every
+/// `len` steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+  pub offset: usize,
+  pub len: usize,
+  /// Steps before the loop starts at which the ghost is on an end node.
+  pub pre_hits: Vec<usize>,
+  /// Steps within the first pass of the loop at which the ghost is on an
+  /// end node; each repeats every `len` steps.
+  pub hits: Vec<usize>,
+}
+
+impl Cycle {
+  pub fn is_hit(&self, t: usize) -> bool {
+    if t < self.offset {
+      self.pre_hits.binary_search(&t).is_ok()
+    } else {
+      let t = self.offset + (t - self.offset) % self.len;
+      self.hits.binary_search(&t).is_ok()
+    }
+  }
+}
+
+/// Combine `t = a (mod n)` and `t = b (mod m)` into a single congruence,
+/// or `None` if they can't both hold. The moduli needn't be coprime.
+fn crt((a, n): (i128, i128), (b, m): (i128, i128)) -> anyhow::Result<Option<(i128, i128)>> {
+  let egcd = n.extended_gcd(&m);
+  let g = egcd.gcd;
+
+  if (b - a) % g != 0 {
+    return Ok(None);
+  }
+
+  let overflow = || anyhow::anyhow!("Overflow combining {} mod {} with {} mod {}", a, n, b, m);
+  let l = (n / g).checked_mul(m).ok_or_else(overflow)?;
+  let k = ((b - a) / g)
+    .checked_mul(egcd.x)
+    .ok_or_else(overflow)?
+    .rem_euclid(m / g);
+  let t = n
+    .checked_mul(k)
+    .and_then(|nk| nk.checked_add(a))
+    .ok_or_else(overflow)?
+    .rem_euclid(l);
+
+  Ok(Some((t, l)))
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -147,4 +613,181 @@ XXX = (XXX, XXX)";
     let m: Map = INPUT.parse().expect("Failed to parse input");
     assert_eq!(m.run_p2(), 13129439557681);
   }
+
+  const CYCLES_INPUT: &'static str = "L
+
+1A = (1B, XX)
+1B = (1Z, XX)
+1Z = (1C, XX)
+1C = (1DZ, XX)
+1DZ = (1B, XX)
+2A = (2B, XX)
+2B = (2C, XX)
+2C = (2Z, XX)
+2Z = (2B, XX)
+3A = (3B, XX)
+3B = (3Z, XX)
+3Z = (3B, XX)
+4A = (4Z, XX)
+4Z = (4B, XX)
+4B = (4B, XX)
+5A = (5Z, XX)
+5Z = (5Z, XX)
+6A = (6Z, XX)
+6Z = (6B, XX)
+6B = (6Z, XX)
+XX = (XX, XX)";
+
+  #[test]
+  fn general_cycles() {
+    let m: Map = CYCLES_INPUT.parse().expect("Failed to parse input");
+    let is_end = |n: &str| n.ends_with("Z");
+
+    assert_eq!(
+      m.cycle("1A", is_end),
+      Cycle {
+        offset: 1,
+        len: 4,
+        pre_hits: vec![],
+        hits: vec![2, 4],
+      }
+    );
+
+    // loops with more than one end node, and which don't start at step 0
+    assert_eq!(m.solve(&["1A", "2A"], is_end).ok(), Some(6));
+    // an answer before some of the ghosts have started looping
+    assert_eq!(m.solve(&["4A", "5A"], is_end).ok(), Some(1));
+    // odd and even steps never line up
+    assert!(m.solve(&["3A", "6A"], is_end).is_err());
+    assert!(m.solve(&["XX"], is_end).is_err());
+
+    // scanning the loops agrees with solving every combination
+    for starts in [["1A", "2A"], ["1A", "3A"], ["2A", "6A"], ["3A", "6A"]] {
+      let cycles: Vec<Cycle> = starts.iter().map(|s| m.cycle(s, is_end)).collect();
+      let lead_in = cycles.iter().map(|c| c.offset).max().unwrap();
+      assert_eq!(
+        scan(&cycles, lead_in).unwrap(),
+        combine(&cycles, lead_in).unwrap()
+      );
+    }
+
+    // a combined period too long to scan is an error, not an endless loop
+    let long: Vec<Cycle> = [999_983, 1_000_003, 1_000_033]
+      .into_iter()
+      .map(|len| Cycle {
+        offset: 0,
+        len,
+        pre_hits: vec![],
+        hits: vec![0],
+      })
+      .collect();
+    assert!(scan(&long, 0).is_err());
+    assert_eq!(combine(&long, 0).unwrap(), Some(0));
+
+    // overflow is an error, not a missed solution
+    let n = 1i128 << 100;
+    assert!(crt((0, n), (1, n + 1)).is_err());
+    assert_eq!(crt((0, 4), (1, 6)).unwrap(), None);
+    assert_eq!(crt((1, 4), (3, 6)).unwrap(), Some((9, 12)));
+  }
+
+  #[test]
+  fn jumps_match_stepping() {
+    let m: Map = INPUT.parse().expect("Failed to parse input");
+    let start = m.id("AAA").unwrap();
+    let jumps = m.jumps();
+
+    let mut at = start;
+    for steps in 0..100_000 {
+      if steps % 997 == 0 {
+        assert_eq!(jumps.after(start, steps), at);
+      }
+      at = m.step_id(at, steps);
+    }
+
+    assert_eq!(jumps.position_after("AAA", 13771), "ZZZ");
+    // whole passes compose, right up to the largest step count
+    let passes = m.pattern.len() * 1000;
+    assert_eq!(
+      jumps.after(start, usize::MAX),
+      jumps.after(jumps.after(start, passes), usize::MAX - passes)
+    );
+    assert!("LR\n\nAAA = (BBB, CCC)".parse::<Map>().is_err());
+
+    // a one step pattern needs a level for every bit of usize
+    let m: Map = "L\n\nAA = (BB, AA)\nBB = (AA, BB)"
+      .parse()
+      .expect("Failed to parse input");
+    let jumps = m.jumps();
+    let mut at = 0;
+    for steps in 0..100 {
+      assert_eq!(jumps.after(0, steps), at);
+      at = m.step_id(at, steps);
+    }
+    assert_eq!(jumps.position_after("AA", usize::MAX), "BB");
+  }
+
+  #[test]
+  fn network_analysis() {
+    let m: Map = EXAMPLE_INPUT.parse().expect("Failed to parse input");
+
+    assert_eq!(
+      m.unreachable_from("AAA"),
+      Some(vec!["BBB", "DDD", "EEE", "GGG"])
+    );
+    assert!(m.to_dot().contains("  \"AAA\" -> \"BBB\" [label=\"L\"];\n"));
+    assert!(m
+      .to_dot()
+      .contains("  \"ZZZ\" [style=filled, fillcolor=red];\n"));
+
+    let ghosts: Map = P2_EXAMPLE_INPUT.parse().expect("Failed to parse input");
+    assert_eq!(ghosts.unreachable_from("AAA"), None);
+    assert_eq!(
+      ghosts.unreachable_from("11A"),
+      Some(vec!["22A", "22B", "22C", "22Z", "XXX"])
+    );
+    let dot = ghosts.to_dot();
+    assert!(dot.contains("  \"11A\" [style=filled, fillcolor=green];\n"));
+    assert!(dot.contains("  \"22C\" -> \"22Z\" [label=\"LR\"];\n"));
+    // every node name is quoted wherever it appears
+    for line in dot.lines().filter(|line| line.starts_with("  ")) {
+      let names = line.split(" [").next().unwrap().split(" -> ");
+      assert!(
+        names.into_iter().all(|n| n.trim().starts_with('"')),
+        "{}",
+        line
+      );
+    }
+
+    let mut components = m.components();
+    components.iter_mut().for_each(|c| c.sort());
+    assert_eq!(components.len(), 7);
+    assert!(components.iter().all(|c| c.len() == 1));
+
+    let m: Map = CYCLES_INPUT.parse().expect("Failed to parse input");
+    assert_eq!(
+      m.stuck_starts(|n| n.ends_with('X'), |n| n.ends_with('Z')),
+      vec!["XX"]
+    );
+
+    let mut components: Vec<Vec<&str>> = m
+      .components()
+      .into_iter()
+      .filter(|c| c.len() > 1)
+      .map(|mut c| {
+        c.sort();
+        c
+      })
+      .collect();
+    components.sort();
+    assert_eq!(
+      components,
+      vec![
+        vec!["1B", "1C", "1DZ", "1Z"],
+        vec!["2B", "2C", "2Z"],
+        vec!["3B", "3Z"],
+        vec!["6B", "6Z"],
+      ]
+    );
+  }
 }
@@ -1,5 +1,5 @@
-use num::Zero;
-use std::{num::ParseIntError, str::FromStr};
+use num::{BigInt, ToPrimitive, Zero};
+use std::{error::Error, fmt::Display, num::ParseIntError, str::FromStr};
 
 const INPUT: &'static str = include_str!("../inputs/day9.txt");
 
@@ -9,7 +9,7 @@ pub enum Side {
 }
 
 #[derive(Debug)]
-struct Report {
+pub struct Report {
   values: Vec<isize>,
 }
 
@@ -29,44 +29,130 @@ impl Report {
     lines.lines().map(|line| line.parse()).collect()
   }
 
-  pub fn solution(&self, side: Side) -> isize {
-    let mut rows = Vec::new();
-    let is_done = |item: &Vec<isize>| item.iter().all(|&value| value.is_zero());
+  pub fn solution(&self, side: Side) -> Result<isize, ReportError> {
+    self.degree()?;
+    self
+      .checked_extrapolate(1, side)
+      .ok_or(ReportError::Overflow)
+  }
 
-    // generate rows until done, starting with the given row
-    rows.push(self.values.clone());
+  /// Return the first value of each row of the difference triangle, down to
+  /// (but not including) the first row of all zeros. These are the
+  /// coefficients of the Newton forward-difference formula.
+  pub fn leading_differences(&self) -> Vec<BigInt> {
+    let mut row: Vec<BigInt> = self.values.iter().map(|&v| BigInt::from(v)).collect();
+    let mut leading = vec![];
 
-    while !is_done(&rows.last().unwrap()) {
-      rows.push(Report::step(&rows.last().unwrap()));
+    while !row.iter().all(|v| v.is_zero()) {
+      leading.push(row[0].clone());
+      row = row.windows(2).map(|pair| &pair[1] - &pair[0]).collect();
     }
 
-    rows
-      .iter()
-      .rev()
-      .map(|row| {
-        *match side {
-          Side::Front => row.first(),
-          Side::Back => row.last(),
-        }
-        .unwrap()
-      })
-      .reduce(|prev, this| match side {
-        Side::Front => this - prev,
-        Side::Back => this + prev,
-      })
-      .unwrap()
-  }
-
-  // given a list of numbers, return a new list that's the difference
-  // between each subsequent pair
-  fn step(values: &[isize]) -> Vec<isize> {
-    values
-      .windows(2)
-      .map(|pair| pair[1] - pair[0])
-      .collect::<Vec<isize>>()
+    leading
+  }
+
+  /// The degree of the polynomial generating this report, or why it can't
+  /// be treated as one.
+  ///
+  /// A report of `n` values always fits some polynomial of degree `n - 1`,
+  /// so it's only known to be polynomial if the differences reach a row of
+  /// zeros before running out of values.
+  pub fn degree(&self) -> Result<usize, ReportError> {
+    let n = self.values.len();
+    let rows = self.leading_differences().len();
+
+    match (n, rows) {
+      (0, _) => Err(ReportError::Empty),
+      (n, rows) if rows < n => Ok(rows.saturating_sub(1)),
+      (n, _) if n < 3 => Err(ReportError::TooShort(n)),
+      (n, _) => Err(ReportError::NotPolynomial(n)),
+    }
+  }
+
+  pub fn is_polynomial(&self) -> bool {
+    self.degree().is_ok()
+  }
+
+  /// Evaluate the sequence at any index, where index 0 is the first value;
+  /// negative indices extrapolate backwards.
+  ///
+  /// Uses `f(x) = sum of Δ^k f(0) * C(x, k)`, with the binomial extended to
+  /// negative `x`, on big integers so it can't overflow. If the report isn't
+  /// a polynomial (see `degree`) this evaluates the lowest degree polynomial
+  /// through its values, which is unlikely to be meaningful.
+  pub fn value_at(&self, index: isize) -> BigInt {
+    let x = BigInt::from(index);
+    let mut binomial = BigInt::from(1);
+    let mut total = BigInt::zero();
+
+    for (k, diff) in self.leading_differences().iter().enumerate() {
+      total += diff * &binomial;
+      // C(x, k + 1) = C(x, k) * (x - k) / (k + 1), which always divides exactly
+      binomial = binomial * (&x - k) / (k + 1);
+    }
+
+    total
+  }
+
+  /// Extrapolate `k` steps past either end of the report.
+  ///
+  /// # Example
+  /// ```
+  /// use aoc::day9::{Report, Side};
+  ///
+  /// let r: Report = "1 3 6 10 15 21".parse().unwrap();
+  ///
+  /// assert_eq!(r.degree(), Ok(2));
+  /// assert_eq!(r.extrapolate(1, Side::Back), 28.into());
+  /// assert_eq!(r.extrapolate(1, Side::Front), 0.into());
+  /// assert_eq!(r.value_at(100), 5151.into());
+  /// ```
+  pub fn extrapolate(&self, k: usize, side: Side) -> BigInt {
+    match side {
+      Side::Front => self.value_at(-(k as isize)),
+      Side::Back => self.value_at((self.values.len() + k) as isize - 1),
+    }
   }
+
+  /// As `extrapolate`, but `None` if the result doesn't fit in an `isize`.
+  pub fn checked_extrapolate(&self, k: usize, side: Side) -> Option<isize> {
+    self.extrapolate(k, side).to_isize()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportError {
+  Empty,
+  /// Too few values to tell whether the report is a polynomial.
+  TooShort(usize),
+  /// The differences of this many values never reach all zeros.
+  NotPolynomial(usize),
+  /// The extrapolated value doesn't fit in an `isize`.
+  Overflow,
 }
 
+impl Display for ReportError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ReportError::Empty => write!(f, "Report has no values"),
+      ReportError::TooShort(n) => write!(
+        f,
+        "Report has only {} value(s); at least 3 are needed to tell if it's a polynomial",
+        n
+      ),
+      ReportError::NotPolynomial(n) => write!(
+        f,
+        "Differences of the {} values never reach zero; not a polynomial of degree below {}",
+        n,
+        n - 1
+      ),
+      ReportError::Overflow => write!(f, "Extrapolated value overflows"),
+    }
+  }
+}
+
+impl Error for ReportError {}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -80,7 +166,10 @@ mod tests {
   fn part1_example() {
     let reports: Vec<Report> = Report::load_all(EXAMPLE_INPUT).expect("Failed to parse lines");
 
-    let ans: Vec<isize> = reports.iter().map(|r| r.solution(Side::Back)).collect();
+    let ans: Vec<isize> = reports
+      .iter()
+      .map(|r| r.solution(Side::Back).unwrap())
+      .collect();
     assert_eq!(&ans, &[18, 28, 68]);
   }
 
@@ -88,7 +177,10 @@ mod tests {
   fn part1_solution() {
     let reports: Vec<Report> = Report::load_all(INPUT).expect("Failed to parse lines");
 
-    let ans: isize = reports.iter().map(|r| r.solution(Side::Back)).sum();
+    let ans: isize = reports
+      .iter()
+      .map(|r| r.solution(Side::Back).unwrap())
+      .sum();
     assert_eq!(ans, 2175229206);
   }
 
@@ -96,7 +188,10 @@ mod tests {
   fn part2_example() {
     let reports: Vec<Report> = Report::load_all(EXAMPLE_INPUT).expect("Failed to parse lines");
 
-    let ans: Vec<isize> = reports.iter().map(|r| r.solution(Side::Front)).collect();
+    let ans: Vec<isize> = reports
+      .iter()
+      .map(|r| r.solution(Side::Front).unwrap())
+      .collect();
     assert_eq!(&ans, &[-3, 0, 5]);
   }
 
@@ -104,7 +199,68 @@ mod tests {
   fn part2_solution() {
     let reports: Vec<Report> = Report::load_all(INPUT).expect("Failed to parse lines");
 
-    let ans: isize = reports.iter().map(|r| r.solution(Side::Front)).sum();
+    let ans: isize = reports
+      .iter()
+      .map(|r| r.solution(Side::Front).unwrap())
+      .sum();
     assert_eq!(ans, 942);
   }
+
+  #[test]
+  fn closed_form() {
+    let reports: Vec<Report> = Report::load_all(INPUT).expect("Failed to parse lines");
+
+    for r in &reports {
+      assert_eq!(
+        r.checked_extrapolate(1, Side::Back),
+        r.solution(Side::Back).ok()
+      );
+      assert_eq!(
+        r.checked_extrapolate(1, Side::Front),
+        r.solution(Side::Front).ok()
+      );
+      for (i, &v) in r.values.iter().enumerate() {
+        assert_eq!(r.value_at(i as isize), BigInt::from(v));
+      }
+    }
+
+    let r: Report = "1 3 6 10 15 21".parse().unwrap();
+    assert_eq!(r.degree(), Ok(2));
+    assert_eq!(r.extrapolate(3, Side::Back), BigInt::from(45));
+    assert_eq!(r.extrapolate(2, Side::Front), BigInt::from(0));
+    assert_eq!(r.value_at(-4), BigInt::from(3));
+
+    let big: Report = "0 1 32 243 1024 3125 7776".parse().unwrap();
+    assert_eq!(big.degree(), Ok(5));
+    assert_eq!(big.checked_extrapolate(1 << 20, Side::Back), None);
+    assert_eq!(
+      big.extrapolate(1 << 20, Side::Back),
+      BigInt::from((1usize << 20) + 6).pow(5)
+    );
+  }
+
+  #[test]
+  fn non_polynomial() {
+    let degree = |s: &str| s.parse::<Report>().unwrap().degree();
+
+    assert_eq!(degree(""), Err(ReportError::Empty));
+    assert_eq!(degree("5"), Err(ReportError::TooShort(1)));
+    assert_eq!(degree("0"), Ok(0));
+    assert_eq!(degree("4 4"), Ok(0));
+    assert_eq!(degree("1 2"), Err(ReportError::TooShort(2)));
+    assert_eq!(degree("1 2 4 8 16 32"), Err(ReportError::NotPolynomial(6)));
+    assert_eq!(degree("1 2 3"), Ok(1));
+
+    let r: Report = "5".parse().unwrap();
+    assert_eq!(r.solution(Side::Back), Err(ReportError::TooShort(1)));
+
+    let r: Report = format!("{} {}", isize::MAX - 1, isize::MAX)
+      .parse()
+      .unwrap();
+    assert_eq!(r.degree(), Err(ReportError::TooShort(2)));
+    let r: Report = format!("{} {} {}", isize::MAX - 2, isize::MAX - 1, isize::MAX)
+      .parse()
+      .unwrap();
+    assert_eq!(r.solution(Side::Back), Err(ReportError::Overflow));
+  }
 }
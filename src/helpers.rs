@@ -27,3 +27,18 @@ macro_rules! parse_error {
     ParseError::new(format!($($arg)*))
   };
 }
+
+/// A small xorshift generator, so randomised tests and benchmarks are
+/// repeatable without pulling in a property testing crate.
+#[cfg(test)]
+pub struct Rng(pub u64);
+
+#[cfg(test)]
+impl Rng {
+  pub fn below(&mut self, n: usize) -> usize {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    (self.0 % n as u64) as usize
+  }
+}